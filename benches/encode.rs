@@ -0,0 +1,50 @@
+//! Compares the throughput of the encoder writing into the different sinks
+//!
+//! Run with `cargo bench --bench encode`
+
+use serde::Serialize;
+use serde_cbored::{
+    ser::Encoder,
+    sink::{IoSink, Sink},
+};
+use std::{hint::black_box, io::BufWriter, time::Instant};
+
+#[derive(Serialize)]
+struct Reading {
+    sensor: &'static str,
+    sequence: u64,
+    values: [i32; 8],
+    valid: bool,
+}
+
+const ITERATIONS: usize = 200_000;
+
+fn bench<S: Sink>(name: &str, mut make_sink: impl FnMut() -> S) {
+    let reading = Reading {
+        sensor: "thermometer",
+        sequence: 42,
+        values: [-3, 7, 1000, -70000, 5, 0, 12, 99],
+        valid: true,
+    };
+    let start = Instant::now();
+    let mut total = 0;
+    for _ in 0..ITERATIONS {
+        let mut encoder = Encoder::new(make_sink());
+        black_box(&reading).serialize(&mut encoder).unwrap();
+        total += encoder.bytes_written();
+        black_box(encoder.into_inner().unwrap());
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name:<24} {:>8.1} MiB/s ({total} bytes in {elapsed:?})",
+        total as f64 / elapsed.as_secs_f64() / (1024.0 * 1024.0)
+    );
+}
+
+fn main() {
+    bench("Vec<u8>", || Vec::with_capacity(128));
+    bench("IoSink<Vec<u8>>", || IoSink::new(Vec::with_capacity(128)));
+    bench("IoSink<BufWriter<Vec>>", || {
+        IoSink::new(BufWriter::new(Vec::with_capacity(128)))
+    });
+}
@@ -0,0 +1,335 @@
+//! Command-line tool to inspect, check and convert CBOR data
+//!
+//! Run `cbored help` for the list of commands and options
+
+use serde::Deserialize;
+use serde_cbored::{
+    __private::{Base64, decode_base64, decode_hex, encode_base64, encode_hex},
+    de::{DecodeOptions, Decoder},
+    diag::to_diag,
+    dump::to_dump,
+    error::DecodeError,
+    json::{from_json, to_json},
+    ser::EncodeOptions,
+    source::SliceSource,
+    to_vec, to_vec_with_options, validate_with_options,
+    value::Value,
+};
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    process::ExitCode,
+};
+
+const USAGE: &str = "\
+Usage: cbored <command> [options] [file]
+
+Reads the input from `file`, or from the standard input when it is missing or `-`
+
+Commands:
+  diag          Print the input in diagnostic notation
+  dump          Print the input as annotated hex
+  validate      Check the input is well-formed
+  to-json       Convert the input into JSON (RFC 8949 section 6.1), one line per data item
+  from-json     Convert JSON into CBOR (RFC 8949 section 6.2)
+  canonicalize  Re-encode the input deterministically (RFC 8949 section 4.2.1)
+  help          Print this message
+
+Options:
+  --input <raw|hex|base64>   Encoding of the CBOR input (default raw)
+  --output <raw|hex|base64>  Encoding of the CBOR output (default raw)
+  --strict                   validate: also check the input is valid (RFC 8949 section 5.3)
+  --deterministic            validate: also check the input is deterministically encoded
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Diag,
+    Dump,
+    Validate,
+    ToJson,
+    FromJson,
+    Canonicalize,
+    Help,
+}
+
+/// How CBOR bytes are written on the command line side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Raw,
+    Hex,
+    Base64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    command: Command,
+    input: Encoding,
+    output: Encoding,
+    strict: bool,
+    deterministic: bool,
+    file: Option<String>,
+}
+
+/// Reasons for the tool to fail, each one with its own exit code
+enum Failure {
+    /// The command line is wrong, exits with 2
+    Usage(String),
+    /// The input is not what the command expects, exits with 1
+    Invalid(String),
+    /// Reading the input or writing the output failed, exits with 1
+    IO(io::Error),
+}
+
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        Self::IO(error)
+    }
+}
+
+fn main() -> ExitCode {
+    let result = parse_args(env::args().skip(1)).and_then(|options| {
+        let output = run(&options)?;
+        io::stdout().lock().write_all(&output)?;
+        Ok(())
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Usage(message)) => {
+            eprintln!("cbored: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(Failure::Invalid(message)) => {
+            eprintln!("cbored: {message}");
+            ExitCode::FAILURE
+        }
+        Err(Failure::IO(error)) => {
+            eprintln!("cbored: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, Failure> {
+    let command = match args.next().as_deref() {
+        Some("diag") => Command::Diag,
+        Some("dump") => Command::Dump,
+        Some("validate") => Command::Validate,
+        Some("to-json") => Command::ToJson,
+        Some("from-json") => Command::FromJson,
+        Some("canonicalize") => Command::Canonicalize,
+        Some("help" | "--help" | "-h") => Command::Help,
+        Some(command) => return Err(Failure::Usage(format!("unknown command `{command}`"))),
+        None => return Err(Failure::Usage(String::from("missing command"))),
+    };
+    let mut options = Options {
+        command,
+        input: Encoding::Raw,
+        output: Encoding::Raw,
+        strict: false,
+        deterministic: false,
+        file: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => options.input = parse_encoding(args.next())?,
+            "--output" => options.output = parse_encoding(args.next())?,
+            "--strict" => options.strict = true,
+            "--deterministic" => options.deterministic = true,
+            "-" => options.file = None,
+            _ if arg.starts_with("--") => {
+                return Err(Failure::Usage(format!("unknown option `{arg}`")));
+            }
+            _ if options.file.is_some() => {
+                return Err(Failure::Usage(String::from("more than one input file")));
+            }
+            _ => options.file = Some(arg),
+        }
+    }
+    Ok(options)
+}
+
+fn parse_encoding(arg: Option<String>) -> Result<Encoding, Failure> {
+    match arg.as_deref() {
+        Some("raw") => Ok(Encoding::Raw),
+        Some("hex") => Ok(Encoding::Hex),
+        Some("base64") => Ok(Encoding::Base64),
+        Some(encoding) => Err(Failure::Usage(format!("unknown encoding `{encoding}`"))),
+        None => Err(Failure::Usage(String::from("missing encoding"))),
+    }
+}
+
+/// Run the command, returning what has to be written to the standard output
+fn run(options: &Options) -> Result<Vec<u8>, Failure> {
+    if options.command == Command::Help {
+        return Ok(USAGE.as_bytes().to_vec());
+    }
+    let input = match &options.file {
+        Some(file) => fs::read(file)?,
+        None => {
+            let mut input = Vec::new();
+            io::stdin().lock().read_to_end(&mut input)?;
+            input
+        }
+    };
+    if options.command == Command::FromJson {
+        let mut output = Vec::new();
+        for json in serde_json::Deserializer::from_slice(&input).into_iter() {
+            let value = from_json(&json.map_err(invalid)?);
+            output.extend(to_vec(&value).map_err(invalid)?);
+        }
+        return Ok(encode(&output, options.output));
+    }
+    let input = decode(&input, options.input)?;
+    match options.command {
+        Command::Diag => Ok(line(to_diag(&input).map_err(invalid)?)),
+        Command::Dump => Ok(to_dump(&input).into_bytes()),
+        Command::Validate => {
+            let validity = DecodeOptions {
+                check_validity: options.strict,
+                ..Default::default()
+            };
+            validate_with_options(&input, validity).map_err(invalid)?;
+            if options.deterministic && canonicalize(&values(&input).map_err(invalid)?) != input {
+                return Err(Failure::Invalid(String::from(
+                    "The input is not deterministically encoded",
+                )));
+            }
+            Ok(Vec::new())
+        }
+        Command::ToJson => {
+            let mut output = Vec::new();
+            for value in values(&input).map_err(invalid)? {
+                serde_json::to_writer(&mut output, &to_json(&value)).map_err(invalid)?;
+                output.push(b'\n');
+            }
+            Ok(output)
+        }
+        Command::Canonicalize => {
+            let output = canonicalize(&values(&input).map_err(invalid)?);
+            Ok(encode(&output, options.output))
+        }
+        Command::FromJson | Command::Help => unreachable!("handled above"),
+    }
+}
+
+fn invalid(error: impl ToString) -> Failure {
+    Failure::Invalid(error.to_string())
+}
+
+fn line(mut text: String) -> Vec<u8> {
+    text.push('\n');
+    text.into_bytes()
+}
+
+/// Decode every data item of the input (a single one or a CBOR sequence)
+fn values(input: &[u8]) -> Result<Vec<Value>, DecodeError> {
+    let mut values = Vec::new();
+    if input.is_empty() {
+        return Ok(values);
+    }
+    let mut decoder = Decoder::new(SliceSource::new(input));
+    loop {
+        values.push(Value::deserialize(&mut decoder)?);
+        if decoder.end().is_ok() {
+            return Ok(values);
+        }
+    }
+}
+
+fn canonicalize(values: &[Value]) -> Vec<u8> {
+    let options = EncodeOptions {
+        sort_maps: true,
+        definite_lengths: true,
+        shortest_floats: true,
+    };
+    let mut output = Vec::new();
+    for value in values {
+        output.extend(to_vec_with_options(value, options).expect("a Value always encodes"));
+    }
+    output
+}
+
+/// Turn the input into raw bytes, whitespace around hex and base64 is ignored
+fn decode(input: &[u8], encoding: Encoding) -> Result<Vec<u8>, Failure> {
+    let text: Vec<u8> = input
+        .iter()
+        .copied()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+    match encoding {
+        Encoding::Raw => Ok(input.to_vec()),
+        Encoding::Hex => {
+            decode_hex(&text).ok_or_else(|| Failure::Invalid(String::from("Invalid hex input")))
+        }
+        Encoding::Base64 => decode_base64(&text)
+            .ok_or_else(|| Failure::Invalid(String::from("Invalid base64 input"))),
+    }
+}
+
+/// Write the CBOR output in the requested encoding, hex and base64 end with a newline
+fn encode(bytes: &[u8], encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Raw => bytes.to_vec(),
+        Encoding::Hex => line(encode_hex(bytes)),
+        Encoding::Base64 => line(encode_base64(bytes, Base64::Standard)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Result<Options, Failure> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn arguments() {
+        let options = args("validate --input hex --strict data.cbor")
+            .ok()
+            .unwrap();
+        assert_eq!(options.command, Command::Validate);
+        assert_eq!(options.input, Encoding::Hex);
+        assert!(options.strict && !options.deterministic);
+        assert_eq!(options.file.as_deref(), Some("data.cbor"));
+        assert!(matches!(args("convert"), Err(Failure::Usage(_))));
+        assert!(matches!(args("diag --input octal"), Err(Failure::Usage(_))));
+        assert!(matches!(args("diag a b"), Err(Failure::Usage(_))));
+    }
+
+    #[test]
+    fn encodings() {
+        for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob", b"\xFB\xFF\x00"] {
+            for encoding in [Encoding::Raw, Encoding::Hex, Encoding::Base64] {
+                let decoded = decode(&encode(bytes, encoding), encoding).ok().unwrap();
+                assert_eq!(decoded, bytes);
+            }
+        }
+        assert_eq!(encode(b"foob", Encoding::Base64), b"Zm9vYg==\n");
+        assert_eq!(
+            decode(b"+_-/", Encoding::Base64).ok().unwrap(),
+            [0xFB, 0xFF, 0xBF]
+        );
+        assert_eq!(
+            decode(b" a1 0B\n", Encoding::Hex).ok().unwrap(),
+            [0xA1, 0x0B]
+        );
+        assert!(decode(b"a", Encoding::Hex).is_err());
+        assert!(decode(b"+1", Encoding::Hex).is_err());
+        assert!(decode(b"Zm9vY", Encoding::Base64).is_err());
+    }
+
+    #[test]
+    fn canonical() {
+        // {"b": 1.5, "a": [_ 1]} with a single precision float
+        let input = b"\xA2\x61b\xFA\x3F\xC0\x00\x00\x61a\x9F\x01\xFF";
+        let decoded = values(input).unwrap();
+        assert_eq!(
+            canonicalize(&decoded),
+            b"\xA2\x61a\x81\x01\x61b\xF9\x3E\x00"
+        );
+        assert_eq!(values(b"\x01\x02").unwrap().len(), 2);
+        assert!(values(b"\x01\x62").is_err());
+    }
+}
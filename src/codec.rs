@@ -0,0 +1,121 @@
+//! Text encodings of byte strings shared by the JSON conversions, the diagnostic notation
+//! parser and the `cbored` tool: base16 (hex) and base64 in both alphabets
+
+use alloc::{string::String, vec::Vec};
+
+/// Alphabets of base64, RFC 4648 sections 4 and 5
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base64 {
+    /// `+` and `/`, written with padding
+    Standard,
+    /// `-` and `_`, written without padding
+    Url,
+}
+
+/// Write `bytes` as lowercase hex digits
+pub fn encode_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let digits = bytes.iter().flat_map(|byte| [byte >> 4, byte & 0x0F]);
+    digits.map(|digit| DIGITS[digit as usize] as char).collect()
+}
+
+/// Read hex digits in either case, [None] for an odd number of them or any other character
+pub fn decode_hex(digits: &[u8]) -> Option<Vec<u8>> {
+    // from_str_radix would take a sign
+    if !digits.len().is_multiple_of(2) || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(core::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Write `bytes` in base64 with the given alphabet
+pub fn encode_base64(bytes: &[u8], alphabet: Base64) -> String {
+    let (symbols, padding): (&[u8; 64], bool) = match alphabet {
+        Base64::Standard => (
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/",
+            true,
+        ),
+        Base64::Url => (
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_",
+            false,
+        ),
+    };
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let mut buffer = [0u8; 4];
+        buffer[1..=chunk.len()].copy_from_slice(chunk);
+        let bits = u32::from_be_bytes(buffer);
+        // Three bytes make four sextets, a partial chunk one more sextet than bytes
+        for index in 0..=chunk.len() {
+            text.push(symbols[(bits >> (18 - 6 * index)) as usize & 0x3F] as char);
+        }
+        if padding {
+            text.extend(core::iter::repeat_n('=', 3 - chunk.len()));
+        }
+    }
+    text
+}
+
+/// Read base64 in either alphabet, with or without padding, [None] for any other character
+/// or a length no bytes encode into
+pub fn decode_base64(digits: &[u8]) -> Option<Vec<u8>> {
+    let digits = digits
+        .strip_suffix(b"==")
+        .or(digits.strip_suffix(b"="))
+        .unwrap_or(digits);
+    if digits.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for digit in digits {
+        let value = match digit {
+            b'A'..=b'Z' => digit - b'A',
+            b'a'..=b'z' => digit - b'a' + 26,
+            b'0'..=b'9' => digit - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        buffer = buffer << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let cases: [(&[u8], &str, &str, &str); 4] = [
+            (b"", "", "", ""),
+            (b"f", "66", "Zg==", "Zg"),
+            (b"fo", "666f", "Zm8=", "Zm8"),
+            (b"\xFB\xFF", "fbff", "+/8=", "-_8"),
+        ];
+        for (bytes, hex, base64, base64url) in cases {
+            assert_eq!(encode_hex(bytes), hex);
+            assert_eq!(encode_base64(bytes, Base64::Standard), base64);
+            assert_eq!(encode_base64(bytes, Base64::Url), base64url);
+            assert_eq!(decode_hex(hex.as_bytes()).unwrap(), bytes);
+            assert_eq!(decode_hex(hex.to_uppercase().as_bytes()).unwrap(), bytes);
+            assert_eq!(decode_base64(base64.as_bytes()).unwrap(), bytes);
+            assert_eq!(decode_base64(base64url.as_bytes()).unwrap(), bytes);
+        }
+        for digits in ["0", "+1", "0g", "-1"] {
+            assert!(decode_hex(digits.as_bytes()).is_none(), "{digits}");
+        }
+        for digits in ["Z", "Zg=A", "Z===", "Zg.="] {
+            assert!(decode_base64(digits.as_bytes()).is_none(), "{digits}");
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! Lazy navigation over encoded data items
+//!
+//! A [Cursor] walks the data items of a byte slice one after the other, only reading
+//! their heads to find where each one ends, and hands them out as [Item]s: their byte
+//! range, which can be stepped into (arrays and maps), looked up by key (maps) or decoded
+//! on its own, borrowing strings from the input
+//!
+//! ```
+//! use serde_cbored::cursor::Cursor;
+//!
+//! // {"type": "ping", "body": h'00...'} where only "type" is needed
+//! let mut message = b"\xA2\x64type\x64ping\x64body\x59\x10\x00".to_vec();
+//! message.resize(message.len() + 0x1000, 0);
+//! let item = Cursor::new(&message).next().unwrap().unwrap();
+//! let kind = item.get("type").unwrap().unwrap();
+//! assert_eq!(kind.as_str(), Some("ping"));
+//! assert_eq!(kind.range(), 6..11);
+//! ```
+//!
+//! # Considerations
+//! - Handing out an item walks the heads inside it to find where it ends, which checks
+//!   it is well-formed, stepping into it walks them again one item at a time
+//! - String contents are always skipped, the validity checks of
+//!   [DecodeOptions::check_validity] and the duplicate keys policy only apply when an
+//!   item is decoded
+
+use crate::{
+    de::DecodeOptions,
+    error::{DecodeError, ValidateError},
+    from_slice_with_options,
+    head::Head,
+    validate::item_end,
+};
+use core::ops::Range;
+use serde::de::Deserialize;
+
+/// Walks a sequence of data items, read [crate::cursor]
+/// # Considerations
+/// - Items are only found by walking the ones before them, skipping an item is reading it
+///   with [Iterator::next] (or [Iterator::nth] for several)
+/// - Iteration stops after the first error
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+    input: &'a [u8],
+    position: usize,
+    end: End,
+    options: DecodeOptions,
+}
+
+/// Where the items a [Cursor] walks stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum End {
+    /// At the end of the input, for a CBOR sequence
+    Input,
+    /// At the break byte closing an indefinite length array or map
+    Break,
+    /// After the given number of items
+    Count(u64),
+    /// Iteration is over, found the end or an error
+    Reached,
+}
+
+impl<'a> Cursor<'a> {
+    /// Construct a cursor over the data items of `input`, a single one or a CBOR sequence
+    pub fn new(input: &'a [u8]) -> Self {
+        Self::with_options(input, DecodeOptions::default())
+    }
+
+    /// Construct a cursor with the given [DecodeOptions], which apply to the items found
+    /// and decoded
+    pub fn with_options(input: &'a [u8], options: DecodeOptions) -> Self {
+        Self {
+            input,
+            position: 0,
+            end: End::Input,
+            options,
+        }
+    }
+
+    /// Where the next item starts in the input
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Read the head of the next item without moving past it, [None] after the last item
+    fn next_head(&mut self) -> Result<Option<(usize, Head)>, ValidateError> {
+        match self.end {
+            End::Reached | End::Count(0) => return Ok(None),
+            End::Input if self.position == self.input.len() => return Ok(None),
+            // 0xFF = break byte
+            End::Break if self.input.get(self.position) == Some(&0xFF) => {
+                self.position += 1;
+                return Ok(None);
+            }
+            _ => {}
+        }
+        let start = self.position;
+        let head = Head::read(&self.input[start..]).map_err(|error| ValidateError {
+            offset: start,
+            error,
+        })?;
+        if let End::Count(remaining) = &mut self.end {
+            *remaining -= 1;
+        }
+        Ok(Some((start, head)))
+    }
+
+    /// Move past the item starting at `start`, returns where it ends
+    fn skip_item(&mut self, start: usize) -> Result<usize, ValidateError> {
+        self.position = item_end(self.input, start, self.options.max_depth)?;
+        Ok(self.position)
+    }
+
+    fn next_item(&mut self) -> Result<Option<Item<'a>>, ValidateError> {
+        let Some((start, head)) = self.next_head()? else {
+            return Ok(None);
+        };
+        let end = self.skip_item(start)?;
+        Ok(Some(Item {
+            input: self.input,
+            start,
+            end,
+            head,
+            options: self.options,
+        }))
+    }
+}
+
+impl<'a> Iterator for Cursor<'a> {
+    type Item = Result<Item<'a>, ValidateError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.next_item();
+        if !matches!(item, Ok(Some(_))) {
+            self.end = End::Reached;
+        }
+        item.transpose()
+    }
+}
+
+/// A well-formed data item found by a [Cursor], read [crate::cursor]
+#[derive(Debug, Clone, Copy)]
+pub struct Item<'a> {
+    input: &'a [u8],
+    start: usize,
+    end: usize,
+    head: Head,
+    options: DecodeOptions,
+}
+
+impl<'a> Item<'a> {
+    /// Where the data item is in the input of the [Cursor]
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// The encoded data item
+    pub fn as_bytes(&self) -> &'a [u8] {
+        &self.input[self.range()]
+    }
+
+    /// Major type of the data item, from 0 (unsigned integer) to 7 (floats and simple values)
+    pub fn major_type(&self) -> u8 {
+        self.head.major_type()
+    }
+
+    /// The text of a definite length text string, [None] for any other data item (including
+    /// indefinite length text strings, use [Item::decode] for those) or invalid UTF-8
+    pub fn as_str(&self) -> Option<&'a str> {
+        match (self.major_type(), self.head.argument) {
+            (3, Some(_)) => {
+                core::str::from_utf8(&self.input[self.start + self.head.size..self.end]).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Decode the data item into `T`, strings may be borrowed from the input
+    pub fn decode<T: Deserialize<'a>>(&self) -> Result<T, DecodeError> {
+        from_slice_with_options(self.as_bytes(), self.options)
+    }
+
+    /// Step into an array, [None] if the data item is not one
+    pub fn items(&self) -> Option<Cursor<'a>> {
+        match self.major_type() {
+            4 => Some(self.contents(1)),
+            _ => None,
+        }
+    }
+
+    /// Step into a map, [None] if the data item is not one
+    pub fn entries(&self) -> Option<Entries<'a>> {
+        match self.major_type() {
+            5 => Some(Entries(self.contents(2))),
+            _ => None,
+        }
+    }
+
+    /// Find the value of the first entry whose key is the text string `key`, [None] if
+    /// there is none or the data item is not a map
+    /// # Considerations
+    /// - Keys are compared from their bytes, so indefinite length keys never match, use
+    ///   [Item::find] to compare them otherwise
+    /// - The entries after the one found are not looked at
+    pub fn get(&self, key: &str) -> Result<Option<Item<'a>>, ValidateError> {
+        let Some(Entries(mut entries)) = self.entries() else {
+            return Ok(None);
+        };
+        while let Some((start, head)) = entries.next_head()? {
+            let end = entries.skip_item(start)?;
+            let found = head.major_type() == 3
+                && head.argument.is_some()
+                && entries.input[start + head.size..end] == *key.as_bytes();
+            // The map was found well-formed, so every key is followed by a value
+            let value = entries.next_item()?.ok_or(ValidateError {
+                offset: entries.position(),
+                error: DecodeError::UnexpectedBreak,
+            })?;
+            if found {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Find the value of the first entry whose key satisfies `predicate`, [None] if there
+    /// is none or the data item is not a map
+    pub fn find(
+        &self,
+        mut predicate: impl FnMut(&Item<'a>) -> bool,
+    ) -> Result<Option<Item<'a>>, ValidateError> {
+        let Some(entries) = self.entries() else {
+            return Ok(None);
+        };
+        for entry in entries {
+            let (key, value) = entry?;
+            if predicate(&key) {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Cursor over the items of an array or map, holding `items_per_entry` items each
+    fn contents(&self, items_per_entry: u64) -> Cursor<'a> {
+        Cursor {
+            input: &self.input[..self.end],
+            position: self.start + self.head.size,
+            end: match self.head.argument {
+                Some(length) => End::Count(length.saturating_mul(items_per_entry)),
+                None => End::Break,
+            },
+            options: self.options,
+        }
+    }
+}
+
+/// Walks the entries of a map, read [Item::entries]
+#[derive(Debug, Clone)]
+pub struct Entries<'a>(Cursor<'a>);
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<(Item<'a>, Item<'a>), ValidateError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = match self.0.next()? {
+            Ok(key) => key,
+            Err(error) => return Some(Err(error)),
+        };
+        // The map was found well-formed, so every key is followed by a value
+        let value = self.0.next().unwrap_or(Err(ValidateError {
+            offset: self.0.position(),
+            error: DecodeError::UnexpectedBreak,
+        }));
+        Some(value.map(|value| (key, value)))
+    }
+}
+
+#[cfg(all(test, feature = "ser", feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::{cbor, to_vec, value::Value};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn navigate() {
+        let value = cbor!({
+            "id" => 7,
+            "type" => "ping",
+            "items" => [1, [2, 3], { "a" => null }],
+        });
+        let input = to_vec(&value).unwrap();
+        let item = Cursor::new(&input).next().unwrap().unwrap();
+        assert_eq!(item.range(), 0..input.len());
+        assert_eq!(item.decode::<Value>().unwrap(), value);
+
+        let kind = item.get("type").unwrap().unwrap();
+        assert_eq!(kind.as_str(), Some("ping"));
+        assert_eq!(kind.decode::<&str>().unwrap(), "ping");
+        assert_eq!(&input[kind.range()], b"\x64ping");
+        assert!(item.get("missing").unwrap().is_none());
+        assert!(kind.get("type").unwrap().is_none());
+        assert!(kind.items().is_none());
+
+        let items = item.get("items").unwrap().unwrap();
+        let nested = items.items().unwrap().nth(1).unwrap().unwrap();
+        let numbers: Vec<u8> = nested
+            .items()
+            .unwrap()
+            .map(|item| item.unwrap().decode().unwrap())
+            .collect();
+        assert_eq!(numbers, [2, 3]);
+        let map = items.items().unwrap().nth(2).unwrap().unwrap();
+        assert_eq!(map.get("a").unwrap().unwrap().as_bytes(), b"\xF6");
+        assert_eq!(item.entries().unwrap().count(), 3);
+    }
+
+    #[test]
+    fn sequences() {
+        // [_ 1, {_ "a": 2}], 3
+        let input = b"\x9F\x01\xBF\x61a\x02\xFF\xFF\x03";
+        let mut cursor = Cursor::new(input);
+        let array = cursor.next().unwrap().unwrap();
+        assert_eq!(array.range(), 0..8);
+        let mut items = array.items().unwrap();
+        assert_eq!(items.next().unwrap().unwrap().as_bytes(), b"\x01");
+        let map = items.next().unwrap().unwrap();
+        assert!(items.next().is_none());
+        assert_eq!(items.position(), 8);
+        assert_eq!(map.get("a").unwrap().unwrap().decode::<u8>().unwrap(), 2);
+        assert_eq!(cursor.next().unwrap().unwrap().as_bytes(), b"\x03");
+        assert!(cursor.next().is_none());
+
+        // A truncated item is reported once where its head starts, 1, [2, 3
+        let mut cursor = Cursor::new(b"\x01\x82\x02");
+        assert!(cursor.next().unwrap().is_ok());
+        let error = cursor.next().unwrap().unwrap_err();
+        assert!(matches!(error.error, DecodeError::UnexpectedEof));
+        assert!(cursor.next().is_none());
+    }
+
+    #[test]
+    fn lazy_validity() {
+        let options = DecodeOptions {
+            check_validity: true,
+            ..Default::default()
+        };
+        // {"type": "ping", "body": {"a": 1, "a": 2}, "text": "\xFF"}
+        let input = b"\xA3\x64type\x64ping\x64body\xA2\x61a\x01\x61a\x02\x64text\x61\xFF";
+        let item = Cursor::with_options(input, options)
+            .next()
+            .unwrap()
+            .unwrap();
+        let kind = item.get("type").unwrap().unwrap();
+        assert_eq!(kind.decode::<&str>().unwrap(), "ping");
+        // The siblings are only checked once decoded
+        let body = item.get("body").unwrap().unwrap();
+        assert!(matches!(
+            body.decode::<Value>(),
+            Err(DecodeError::DuplicateKey)
+        ));
+        let text = item.get("text").unwrap().unwrap();
+        assert_eq!(text.as_str(), None);
+        assert!(matches!(
+            text.decode::<Value>(),
+            Err(DecodeError::InvalidUtf8)
+        ));
+        assert!(item.decode::<Value>().is_err());
+        // The first of the duplicate entries is found
+        let first = body.get("a").unwrap().unwrap();
+        assert_eq!(first.decode::<u8>().unwrap(), 1);
+        assert!(item.get("typ").unwrap().is_none());
+    }
+}
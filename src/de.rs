@@ -1,136 +1,735 @@
 //! The CBOR decoder
 
-#![allow(unused)]
+use crate::{
+    error::DecodeError,
+    float::f16_to_f64,
+    raw::RAW_NAME,
+    simple::SIMPLE_NAME,
+    source::{Reference, Source},
+    tag::{TAG_NAME, is_valid_content},
+};
+#[cfg(feature = "alloc")]
+use crate::{
+    from_slice_with_options,
+    source::{CopiedSource, SliceSource},
+    value::{VALUE_NAME, Value},
+};
+#[cfg(feature = "alloc")]
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+use serde::de::{
+    DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor, value::BorrowedStrDeserializer,
+};
 
-use crate::error::DecodeError;
-use serde::de::{Deserializer, Visitor};
-use std::io::{BufReader, Read};
+/// Options changing the behaviour of the [Decoder]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Maximum nesting depth of arrays, maps and tags, deeper data is rejected
+    /// with [DecodeError::DepthLimitExceeded] instead of exhausting the stack
+    pub max_depth: usize,
+    /// Check the data items are valid and not only well-formed (RFC 8949 section 5.3):
+    /// text strings have to be valid UTF-8 even when skipped and every chunk on its own,
+    /// tags have to enclose the type of data item they require (e.g. a text string for
+    /// tag 0, a byte string for tag 2) and maps cannot hold the same key twice, unless
+    /// [DecodeOptions::duplicate_keys] says what to do with them
+    /// # Considerations
+    /// - Without the `alloc` feature duplicate keys are not looked for
+    pub check_validity: bool,
+    /// What to do with maps holding the same key more than once, read [DuplicateKeys]
+    /// # Considerations
+    /// - Keys are compared from their bytes, kept by the [Source] while they are read
+    ///   (read [Source::start_recording]), sources unable to keep them fail with
+    ///   [DecodeError::RawValueUnsupported]
+    /// - [DuplicateKeys::KeepFirst] and [DuplicateKeys::KeepLast] read each map ahead to
+    ///   find the entries kept, then read it again from its kept bytes
+    #[cfg(feature = "alloc")]
+    pub duplicate_keys: DuplicateKeys,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 128,
+            check_validity: false,
+            #[cfg(feature = "alloc")]
+            duplicate_keys: DuplicateKeys::Unchecked,
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// The policy actually applied, [DecodeOptions::check_validity] rejects duplicate
+    /// keys when no policy is chosen
+    #[cfg(feature = "alloc")]
+    pub(crate) fn duplicate_key_policy(&self) -> DuplicateKeys {
+        match (self.duplicate_keys, self.check_validity) {
+            (DuplicateKeys::Unchecked, true) => DuplicateKeys::Reject,
+            (policy, _) => policy,
+        }
+    }
+}
 
-pub struct Decoder<R: Read> {
-    reader: BufReader<R>,
+/// What the [Decoder] does with a map holding the same key more than once (RFC 8949
+/// section 5.6), keys are the same when their [Value](crate::value::Value)s are equal
+/// (e.g. `1` encoded in one byte and in two bytes)
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Keys are not compared, every entry is handed to the type being deserialized, which
+    /// decides (e.g. a `BTreeMap` keeps the last one, a derived struct fails)
+    #[default]
+    Unchecked,
+    /// Fail with [DecodeError::DuplicateKey]
+    /// # Considerations
+    /// - Each key is read a second time from its bytes into a [Value], the keys of a map
+    ///   are kept until its end, at every nesting level
+    Reject,
+    /// Keep the entry found first, dropping the later ones
+    /// # Considerations
+    /// - Each map is read twice, once ahead decoding its keys into [Value]s to find the
+    ///   entries kept, then again from its bytes, so a map nested `n` levels deep is read
+    ///   `n + 1` times
+    /// - The bytes of the whole map are held while it is read again, copied into memory
+    ///   when the [Source] does not hold them already (e.g. an
+    ///   [IoSource](crate::source::IoSource)), so decoding from a reader no longer streams
+    ///   maps in bounded memory
+    KeepFirst,
+    /// Keep the entry found last, where it was found
+    /// # Considerations
+    /// - Costs as much as [DuplicateKeys::KeepFirst]
+    KeepLast,
 }
 
-impl<R: Read> Decoder<R> {
-    pub fn new(source: R) -> Self {
+/// The decoder type
+/// # Considerations
+/// - The decoder reads from a [Source], when it is a [SliceSource](crate::source::SliceSource)
+///   byte and text strings are borrowed from the input instead of copied
+pub struct Decoder<S> {
+    source: S,
+    options: DecodeOptions,
+    depth: usize,
+    /// Buffer where the chunks of indefinite length strings are concatenated
+    #[cfg(feature = "alloc")]
+    scratch: Vec<u8>,
+}
+
+/// The header of a data item, its initial byte plus its argument
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Header {
+    Unsigned(u64),
+    Negative(u64),
+    Bytes(Option<u64>),
+    Text(Option<u64>),
+    Array(Option<u64>),
+    Map(Option<u64>),
+    Tag(u64),
+    Simple(u8),
+    Float(f64),
+    Break,
+}
+
+impl Header {
+    /// Human readable name of the data item, used in error messages
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Header::Unsigned(_) => "unsigned integer",
+            Header::Negative(_) => "negative integer",
+            Header::Bytes(_) => "byte string",
+            Header::Text(_) => "text string",
+            Header::Array(_) => "array",
+            Header::Map(_) => "map",
+            Header::Tag(_) => "tag",
+            Header::Simple(20 | 21) => "boolean",
+            Header::Simple(22) => "null",
+            Header::Simple(23) => "undefined",
+            Header::Simple(_) => "simple value",
+            Header::Float(_) => "float",
+            Header::Break => "break",
+        }
+    }
+}
+
+impl<'de, S: Source<'de>> Decoder<S> {
+    /// Construct a new decoder, which will read its input from `S`
+    pub fn new(source: S) -> Self {
+        Self::with_options(source, DecodeOptions::default())
+    }
+
+    /// Construct a new decoder with the given [DecodeOptions]
+    pub fn with_options(source: S, options: DecodeOptions) -> Self {
         Self {
-            reader: BufReader::new(source),
+            source,
+            options,
+            depth: 0,
+            #[cfg(feature = "alloc")]
+            scratch: Vec::new(),
         }
     }
 
+    /// Return the source, positioned right after the last decoded data item
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    /// Ensure there is no data left in the input, this should be called after
+    /// decoding the last expected data item so trailing garbage is not silently ignored
+    pub fn end(&mut self) -> Result<(), DecodeError> {
+        match self.peek_u8()? {
+            Some(_) => Err(DecodeError::TrailingData),
+            None => Ok(()),
+        }
+    }
+
+    fn peek_u8(&mut self) -> Result<Option<u8>, DecodeError> {
+        self.source.peek_u8()
+    }
+
     fn read_u8(&mut self) -> Result<u8, DecodeError> {
         let mut u8_buf: [u8; 1] = [0; 1];
-        self.reader.read_exact(&mut u8_buf)?;
+        self.source.read_exact(&mut u8_buf)?;
         Ok(u8_buf[0])
     }
 
     fn read_u16(&mut self) -> Result<u16, DecodeError> {
         let mut u16_buf: [u8; 2] = [0; 2];
-        self.reader.read_exact(&mut u16_buf)?;
+        self.source.read_exact(&mut u16_buf)?;
         Ok(u16::from_be_bytes(u16_buf))
     }
 
     fn read_u32(&mut self) -> Result<u32, DecodeError> {
         let mut u32_buf: [u8; 4] = [0; 4];
-        self.reader.read_exact(&mut u32_buf)?;
+        self.source.read_exact(&mut u32_buf)?;
         Ok(u32::from_be_bytes(u32_buf))
     }
 
     fn read_u64(&mut self) -> Result<u64, DecodeError> {
         let mut u64_buf: [u8; 8] = [0; 8];
-        self.reader.read_exact(&mut u64_buf)?;
+        self.source.read_exact(&mut u64_buf)?;
         Ok(u64::from_be_bytes(u64_buf))
     }
 
-    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), DecodeError> {
-        Ok(self.reader.read_exact(buf)?)
+    /// Read the argument of a header given its additional information,
+    /// [None] means indefinite length
+    fn read_argument(&mut self, initial_byte: u8) -> Result<Option<u64>, DecodeError> {
+        match initial_byte & 0x1F {
+            additional_information @ 0..24 => Ok(Some(additional_information as u64)),
+            24 => Ok(Some(self.read_u8()? as u64)),
+            25 => Ok(Some(self.read_u16()? as u64)),
+            26 => Ok(Some(self.read_u32()? as u64)),
+            27 => Ok(Some(self.read_u64()?)),
+            31 => Ok(None),
+            // 28, 29 and 30 are reserved
+            _ => Err(DecodeError::MalformedHeader(initial_byte)),
+        }
+    }
+
+    pub(crate) fn read_header(&mut self) -> Result<Header, DecodeError> {
+        let initial_byte = self.read_u8()?;
+        let argument = self.read_argument(initial_byte)?;
+        let definite =
+            |argument: Option<u64>| argument.ok_or(DecodeError::MalformedHeader(initial_byte));
+        match initial_byte >> 5 {
+            0 => Ok(Header::Unsigned(definite(argument)?)),
+            1 => Ok(Header::Negative(definite(argument)?)),
+            2 => Ok(Header::Bytes(argument)),
+            3 => Ok(Header::Text(argument)),
+            4 => Ok(Header::Array(argument)),
+            5 => Ok(Header::Map(argument)),
+            6 => {
+                let tag = definite(argument)?;
+                self.check_tag_content(tag)?;
+                Ok(Header::Tag(tag))
+            }
+            _ => match (initial_byte & 0x1F, argument) {
+                // 0xF8 = simple value in the next byte, values below 32 do not need it
+                (24, Some(value)) if value < 32 => {
+                    Err(DecodeError::ReservedSimpleValue(value as u8))
+                }
+                (24, Some(value)) => Ok(Header::Simple(value as u8)),
+                // 0xF9 = half precision float
+                (25, Some(value)) => Ok(Header::Float(f16_to_f64(value as u16))),
+                // 0xFA = single precision float
+                (26, Some(value)) => Ok(Header::Float(f32::from_bits(value as u32) as f64)),
+                // 0xFB = double precision float
+                (27, Some(value)) => Ok(Header::Float(f64::from_bits(value))),
+                // 0xFF = break byte
+                (31, None) => Ok(Header::Break),
+                (_, Some(value)) => Ok(Header::Simple(value as u8)),
+                (_, None) => Err(DecodeError::MalformedHeader(initial_byte)),
+            },
+        }
+    }
+
+    /// Read the contents of a byte or text string, concatenating the chunks
+    /// of an indefinite length one
+    fn read_string(
+        &mut self,
+        major_type: u8,
+        length: Option<u64>,
+    ) -> Result<Reference<'de, '_>, DecodeError> {
+        match length {
+            Some(length) => self.source.read_slice(length),
+            #[cfg(feature = "alloc")]
+            None => {
+                self.scratch.clear();
+                loop {
+                    match (major_type, self.read_header()?) {
+                        (2, Header::Bytes(Some(length))) | (3, Header::Text(Some(length))) => {
+                            let chunk = self.source.read_slice(length)?;
+                            // A character cannot be split between chunks
+                            if major_type == 3 && self.options.check_validity {
+                                to_str(chunk.as_ref())?;
+                            }
+                            self.scratch.extend_from_slice(chunk.as_ref());
+                        }
+                        (_, Header::Break) => break,
+                        _ => return Err(DecodeError::InvalidChunk),
+                    }
+                }
+                Ok(Reference::Copied(&self.scratch))
+            }
+            #[cfg(not(feature = "alloc"))]
+            None => {
+                let _ = major_type;
+                Err(DecodeError::UnsupportedIndefiniteString)
+            }
+        }
+    }
+
+    /// Skip the contents of a definite length string, text strings are still checked
+    /// when [DecodeOptions::check_validity] is set
+    fn skip_string(&mut self, major_type: u8, length: u64) -> Result<(), DecodeError> {
+        match major_type == 3 && self.options.check_validity {
+            true => to_str(self.source.read_slice(length)?.as_ref()).map(|_| ()),
+            false => self.source.skip(length),
+        }
+    }
+
+    /// Fail with [DecodeError::InvalidTagContent] if [DecodeOptions::check_validity] is
+    /// set and the next data item cannot be the content of `tag`
+    fn check_tag_content(&mut self, tag: u64) -> Result<(), DecodeError> {
+        if !self.options.check_validity {
+            return Ok(());
+        }
+        match self.peek_u8()? {
+            Some(initial_byte) if !is_valid_content(tag, initial_byte) => {
+                Err(DecodeError::InvalidTagContent(tag))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Skip the chunks of an indefinite length string
+    fn skip_chunks(&mut self, major_type: u8) -> Result<(), DecodeError> {
+        loop {
+            match (major_type, self.read_header()?) {
+                (2, Header::Bytes(Some(length))) | (3, Header::Text(Some(length))) => {
+                    self.skip_string(major_type, length)?
+                }
+                (_, Header::Break) => return Ok(()),
+                _ => return Err(DecodeError::InvalidChunk),
+            }
+        }
+    }
+
+    /// Run `f` one nesting level deeper, enforcing [DecodeOptions::max_depth]
+    fn recurse<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<T, DecodeError> {
+        if self.depth >= self.options.max_depth {
+            return Err(DecodeError::DepthLimitExceeded);
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Consume the break byte closing an indefinite length container,
+    /// returns whether it was found
+    fn consume_break(&mut self) -> Result<bool, DecodeError> {
+        // 0xFF = break byte
+        if self.peek_u8()? == Some(0xFF) {
+            self.read_u8()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Skip a whole data item without interpreting it
+    fn skip_item(&mut self) -> Result<(), DecodeError> {
+        match self.read_header()? {
+            Header::Bytes(Some(length)) => self.skip_string(2, length),
+            Header::Text(Some(length)) => self.skip_string(3, length),
+            Header::Bytes(None) => self.skip_chunks(2),
+            Header::Text(None) => self.skip_chunks(3),
+            Header::Array(length) => self.recurse(|decoder| decoder.skip_items(length, 1)),
+            Header::Map(length) => self.recurse(|decoder| decoder.skip_items(length, 2)),
+            Header::Tag(_) => self.recurse(|decoder| decoder.skip_item()),
+            Header::Break => Err(DecodeError::UnexpectedBreak),
+            _ => Ok(()),
+        }
+    }
+
+    fn skip_items(&mut self, length: Option<u64>, items_per_entry: u64) -> Result<(), DecodeError> {
+        match length {
+            Some(length) => {
+                for _ in 0..length.saturating_mul(items_per_entry) {
+                    self.skip_item()?;
+                }
+            }
+            None => {
+                while !self.consume_break()? {
+                    for _ in 0..items_per_entry {
+                        self.skip_item()?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Skip the next data item and hand out its bytes as they are encoded, read [crate::raw]
+    fn deserialize_raw<V>(&mut self, visitor: V) -> Result<V::Value, DecodeError>
+    where
+        V: Visitor<'de>,
+    {
+        let mark = self.source.start_recording();
+        let skipped = self.skip_item();
+        let raw = self.source.finish_recording(mark);
+        skipped?;
+        // Skipping does not compare map keys, the captured bytes are walked again for that
+        #[cfg(feature = "alloc")]
+        if self.options.duplicate_key_policy() == DuplicateKeys::Reject
+            && let Ok(raw) = &raw
+        {
+            crate::validate::validate(raw.as_ref(), self.options).map_err(|error| error.error)?;
+        }
+        match raw? {
+            Reference::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+            Reference::Copied(bytes) => visitor.visit_bytes(bytes),
+        }
+    }
+
+    /// Deserialize any data item like [Deserializer::deserialize_any] but handing out
+    /// tags, undefined and unassigned simple values as an enum, read [crate::value]
+    #[cfg(feature = "alloc")]
+    fn deserialize_value<V>(&mut self, visitor: V) -> Result<V::Value, DecodeError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.read_header()? {
+            Header::Tag(tag) => self.recurse(|decoder| {
+                visitor.visit_enum(SpecialDecoder::Tag(TagDecoder {
+                    decoder,
+                    tag,
+                    remaining: 2,
+                }))
+            }),
+            Header::Simple(value) if !(20..23).contains(&value) => {
+                visitor.visit_enum(SpecialDecoder::<S>::Simple(value))
+            }
+            header => self.visit_header(header, visitor),
+        }
+    }
+
+    /// Deserialize an integer which may be a bignum (RFC 8949 section 3.4.3), as
+    /// written by the [Encoder](crate::ser::Encoder) for 128 bit integers
+    fn deserialize_bignum<V>(&mut self, visitor: V) -> Result<V::Value, DecodeError>
+    where
+        V: Visitor<'de>,
+    {
+        // 0xC2 = tag(2), unsigned bignum | 0xC3 = tag(3), negative bignum
+        let negative = match self.peek_u8()? {
+            Some(0xC2) => false,
+            Some(0xC3) => true,
+            _ => return self.deserialize_any(visitor),
+        };
+        self.read_u8()?;
+        self.check_tag_content(2 + negative as u64)?;
+        let magnitude = match self.read_header()? {
+            Header::Bytes(length) => bignum_magnitude(self.read_string(2, length)?.as_ref())?,
+            header => {
+                return Err(DecodeError::TypeMismatch {
+                    expected: "byte string",
+                    found: header.kind(),
+                });
+            }
+        };
+        match (negative, i128::try_from(magnitude)) {
+            (false, _) => visitor.visit_u128(magnitude),
+            (true, Ok(magnitude)) => visitor.visit_i128(-1 - magnitude),
+            (true, Err(_)) => Err(DecodeError::IntegerOverflow),
+        }
+    }
+
+    /// Run `f`, which reads a map key, and decode the bytes it consumed into a [Value]
+    /// to compare the key with the other ones
+    #[cfg(feature = "alloc")]
+    fn record_key<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<(T, Value), DecodeError> {
+        let mark = self.source.start_recording();
+        let result = f(self);
+        let recorded = self.source.finish_recording(mark);
+        let result = result?;
+        let key = from_slice_with_options(recorded?.as_ref(), self.options)?;
+        Ok((result, key))
+    }
+
+    /// Read the entries of a map ahead to find the ones `policy` keeps, then hand them
+    /// to `visitor` reading the map again from its bytes, read [DecodeOptions::duplicate_keys]
+    #[cfg(feature = "alloc")]
+    fn visit_kept_map<V>(
+        &mut self,
+        length: Option<u64>,
+        policy: DuplicateKeys,
+        visitor: V,
+    ) -> Result<V::Value, DecodeError>
+    where
+        V: Visitor<'de>,
+    {
+        let (options, depth) = (self.options, self.depth);
+        let mark = self.source.start_recording();
+        let keys = self.skip_entries(length);
+        let recorded = self.source.finish_recording(mark);
+        let keys = keys?;
+        // Whether each entry is the first one of its key, looking from the end to keep the last
+        let mut found = BTreeSet::new();
+        let mut kept: Vec<bool> = match policy {
+            DuplicateKeys::KeepLast => keys.iter().rev().map(|key| found.insert(key)).collect(),
+            _ => keys.iter().map(|key| found.insert(key)).collect(),
+        };
+        if policy == DuplicateKeys::KeepLast {
+            kept.reverse();
+        }
+        // Borrowed bytes live for 'de, so strings read again are still borrowed from the input
+        match recorded? {
+            Reference::Borrowed(bytes) => Decoder::replay(SliceSource::new(bytes), options, depth)
+                .visit_kept_entries(length, kept, visitor),
+            Reference::Copied(bytes) => Decoder::replay(CopiedSource::new(bytes), options, depth)
+                .visit_kept_entries(length, kept, visitor),
+        }
+    }
+
+    /// Skip the entries of a map, returning their keys
+    #[cfg(feature = "alloc")]
+    fn skip_entries(&mut self, length: Option<u64>) -> Result<Vec<Value>, DecodeError> {
+        let mut entries = SeqDecoder::new(self, length);
+        let mut keys = Vec::new();
+        while entries.has_next()? {
+            let ((), key) = entries.decoder.record_key(Self::skip_item)?;
+            keys.push(key);
+            entries.decoder.skip_item()?;
+        }
+        Ok(keys)
+    }
+
+    /// Construct a decoder reading again the entries of a map at nesting level `depth`
+    #[cfg(feature = "alloc")]
+    fn replay(source: S, options: DecodeOptions, depth: usize) -> Self {
+        Self {
+            depth,
+            ..Self::with_options(source, options)
+        }
+    }
+
+    /// Hand the entries of a map read by [Decoder::visit_kept_map] to `visitor`,
+    /// skipping the ones not `kept`
+    #[cfg(feature = "alloc")]
+    fn visit_kept_entries<V>(
+        &mut self,
+        length: Option<u64>,
+        kept: Vec<bool>,
+        visitor: V,
+    ) -> Result<V::Value, DecodeError>
+    where
+        V: Visitor<'de>,
+    {
+        let mut access = KeptMapDecoder {
+            entries: MapDecoder::new(self, length),
+            kept: kept.into_iter(),
+        };
+        let value = visitor.visit_map(&mut access)?;
+        access.entries.end()?;
+        Ok(value)
+    }
+
+    fn visit_header<V>(&mut self, header: Header, visitor: V) -> Result<V::Value, DecodeError>
+    where
+        V: Visitor<'de>,
+    {
+        match header {
+            Header::Unsigned(value) => visitor.visit_u64(value),
+            Header::Negative(value) => match i64::try_from(value) {
+                Ok(value) => visitor.visit_i64(-1 - value),
+                Err(_) => visitor.visit_i128(-1 - value as i128),
+            },
+            Header::Bytes(length) => match self.read_string(2, length)? {
+                Reference::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+                Reference::Copied(bytes) => visitor.visit_bytes(bytes),
+            },
+            Header::Text(length) => match self.read_string(3, length)? {
+                Reference::Borrowed(bytes) => visitor.visit_borrowed_str(to_str(bytes)?),
+                Reference::Copied(bytes) => visitor.visit_str(to_str(bytes)?),
+            },
+            Header::Array(length) => self.recurse(|decoder| {
+                let mut access = SeqDecoder::new(decoder, length);
+                let value = visitor.visit_seq(&mut access)?;
+                access.end()?;
+                Ok(value)
+            }),
+            Header::Map(length) => self.recurse(|decoder| {
+                #[cfg(feature = "alloc")]
+                match decoder.options.duplicate_key_policy() {
+                    DuplicateKeys::Unchecked => {}
+                    DuplicateKeys::Reject => {
+                        let mut access = UniqueMapDecoder {
+                            entries: MapDecoder::new(decoder, length),
+                            keys: BTreeSet::new(),
+                        };
+                        let value = visitor.visit_map(&mut access)?;
+                        access.entries.end()?;
+                        return Ok(value);
+                    }
+                    policy => return decoder.visit_kept_map(length, policy, visitor),
+                }
+                let mut access = MapDecoder::new(decoder, length);
+                let value = visitor.visit_map(&mut access)?;
+                access.end()?;
+                Ok(value)
+            }),
+            // Tags are transparent to the serde data model
+            Header::Tag(_) => self.recurse(|decoder| decoder.deserialize_any(visitor)),
+            Header::Simple(20) => visitor.visit_bool(false),
+            Header::Simple(21) => visitor.visit_bool(true),
+            // Both null and undefined are treated as the absence of a value
+            Header::Simple(22 | 23) => visitor.visit_unit(),
+            Header::Simple(value) => Err(DecodeError::UnsupportedSimpleValue(value)),
+            Header::Float(value) => visitor.visit_f64(value),
+            Header::Break => Err(DecodeError::UnexpectedBreak),
+        }
     }
 }
 
-impl<'de, R: Read> Deserializer<'de> for &mut Decoder<R> {
+/// Value of the big endian bytes of a bignum
+pub(crate) fn bignum_magnitude(bytes: &[u8]) -> Result<u128, DecodeError> {
+    let significant = &bytes[bytes.iter().take_while(|byte| **byte == 0).count()..];
+    if significant.len() > 16 {
+        return Err(DecodeError::IntegerOverflow);
+    }
+    Ok(significant
+        .iter()
+        .fold(0, |magnitude, byte| magnitude << 8 | *byte as u128))
+}
+
+fn to_str(bytes: &[u8]) -> Result<&str, DecodeError> {
+    core::str::from_utf8(bytes).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+impl<'de, S: Source<'de>> Deserializer<'de> for &mut Decoder<S> {
     type Error = DecodeError;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        let header = self.read_header()?;
+        self.visit_header(header, visitor)
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bignum(visitor)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bignum(visitor)
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     // Even though CBOR RFC mandates all text strings to be valid UTF-8 we do check for correctness
@@ -138,62 +737,71 @@ impl<'de, R: Read> Deserializer<'de> for &mut Decoder<R> {
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        match self.peek_u8()? {
+            // 0xF6 = null | 0xF7 = undefined
+            Some(0xF6 | 0xF7) => {
+                self.read_u8()?;
+                visitor.visit_none()
+            }
+            _ => visitor.visit_some(self),
+        }
     }
 
     fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_unit_struct<V>(
         self,
-        name: &'static str,
+        _name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
+    // Simple values are handed out as their number, read [crate::simple], and raw
+    // values as their bytes, read [crate::raw]
     fn deserialize_newtype_struct<V>(
         self,
         name: &'static str,
@@ -202,54 +810,98 @@ impl<'de, R: Read> Deserializer<'de> for &mut Decoder<R> {
     where
         V: Visitor<'de>,
     {
-        todo!()
+        #[cfg(feature = "alloc")]
+        if name == VALUE_NAME {
+            return self.deserialize_value(visitor);
+        }
+        if name == RAW_NAME {
+            return self.deserialize_raw(visitor);
+        }
+        if name != SIMPLE_NAME {
+            return visitor.visit_newtype_struct(self);
+        }
+        match self.read_header()? {
+            Header::Simple(value) => visitor.visit_u8(value),
+            header => Err(DecodeError::TypeMismatch {
+                expected: "simple value",
+                found: header.kind(),
+            }),
+        }
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
-    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
+    // Types asking for tags get tagged data items as a sequence of the tag number and
+    // the enclosed value, and untagged ones as a newtype, read [crate::tag]
     fn deserialize_tuple_struct<V>(
         self,
         name: &'static str,
-        len: usize,
+        _len: usize,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        if name != TAG_NAME {
+            return self.deserialize_any(visitor);
+        }
+        match self.peek_u8()? {
+            // 0xC0..=0xDF = tag major type
+            Some(0xC0..=0xDF) => match self.read_header()? {
+                Header::Tag(tag) => self.recurse(|decoder| {
+                    let mut access = TagDecoder {
+                        decoder,
+                        tag,
+                        remaining: 2,
+                    };
+                    let value = visitor.visit_seq(&mut access)?;
+                    match access.remaining {
+                        0 => Ok(value),
+                        _ => Err(DecodeError::LengthMismatch),
+                    }
+                }),
+                header => Err(DecodeError::TypeMismatch {
+                    expected: "tag",
+                    found: header.kind(),
+                }),
+            },
+            _ => visitor.visit_newtype_struct(self),
+        }
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_struct<V>(
         self,
-        name: &'static str,
-        fields: &'static [&'static str],
+        _name: &'static str,
+        _fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
+    // The encoder writes unit variants as a text string holding the variant name and
+    // every other variant as an array whose first element is the variant name
     fn deserialize_enum<V>(
         self,
         name: &'static str,
@@ -259,20 +911,658 @@ impl<'de, R: Read> Deserializer<'de> for &mut Decoder<R> {
     where
         V: Visitor<'de>,
     {
-        todo!()
+        match self.read_header()? {
+            Header::Text(length) => match self.read_string(3, length)? {
+                Reference::Borrowed(bytes) => {
+                    visitor.visit_enum(BorrowedStrDeserializer::new(to_str(bytes)?))
+                }
+                Reference::Copied(bytes) => visitor.visit_enum(to_str(bytes)?.into_deserializer()),
+            },
+            Header::Array(length) => self.recurse(|decoder| {
+                let length = match length {
+                    Some(0) => return Err(DecodeError::LengthMismatch),
+                    Some(length) => Some(length - 1),
+                    None => None,
+                };
+                visitor.visit_enum(EnumDecoder {
+                    decoder,
+                    remaining: length,
+                })
+            }),
+            Header::Tag(_) => {
+                self.recurse(|decoder| decoder.deserialize_enum(name, variants, visitor))
+            }
+            header => Err(DecodeError::TypeMismatch {
+                expected: "enum",
+                found: header.kind(),
+            }),
+        }
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.skip_item()?;
+        visitor.visit_unit()
+    }
+}
+
+/// Gives access to the items of an array
+struct SeqDecoder<'decoder, S> {
+    decoder: &'decoder mut Decoder<S>,
+    /// Items left to read, [None] for indefinite length arrays
+    remaining: Option<u64>,
+    finished: bool,
+}
+
+impl<'de, 'decoder, S: Source<'de>> SeqDecoder<'decoder, S> {
+    fn new(decoder: &'decoder mut Decoder<S>, length: Option<u64>) -> Self {
+        Self {
+            decoder,
+            remaining: length,
+            finished: length == Some(0),
+        }
+    }
+
+    fn has_next(&mut self) -> Result<bool, DecodeError> {
+        if self.finished {
+            return Ok(false);
+        }
+        match &mut self.remaining {
+            Some(remaining) => {
+                *remaining -= 1;
+                self.finished = *remaining == 0;
+                Ok(true)
+            }
+            None => {
+                self.finished = self.decoder.consume_break()?;
+                Ok(!self.finished)
+            }
+        }
+    }
+
+    /// Ensure the visitor consumed every item, visitors knowing how many items
+    /// they want may leave the break byte of an indefinite length array behind
+    fn end(&mut self) -> Result<(), DecodeError> {
+        if self.finished || (self.remaining.is_none() && self.decoder.consume_break()?) {
+            Ok(())
+        } else {
+            Err(DecodeError::LengthMismatch)
+        }
+    }
+}
+
+impl<'de, 'decoder, S: Source<'de>> SeqAccess<'de> for SeqDecoder<'decoder, S> {
+    type Error = DecodeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.has_next()? {
+            seed.deserialize(&mut *self.decoder).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining
+            .and_then(|remaining| usize::try_from(remaining).ok())
+    }
+}
+
+/// Gives access to the entries of a map
+struct MapDecoder<'decoder, S> {
+    entries: SeqDecoder<'decoder, S>,
+}
+
+impl<'de, 'decoder, S: Source<'de>> MapDecoder<'decoder, S> {
+    fn new(decoder: &'decoder mut Decoder<S>, length: Option<u64>) -> Self {
+        Self {
+            entries: SeqDecoder::new(decoder, length),
+        }
+    }
+
+    fn end(&mut self) -> Result<(), DecodeError> {
+        self.entries.end()
+    }
+}
+
+impl<'de, 'decoder, S: Source<'de>> MapAccess<'de> for MapDecoder<'decoder, S> {
+    type Error = DecodeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        self.entries.next_element_seed(seed)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.entries.decoder)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.entries.size_hint()
+    }
+}
+
+/// Gives access to the entries of a map failing with [DecodeError::DuplicateKey]
+/// when a key is found again
+#[cfg(feature = "alloc")]
+struct UniqueMapDecoder<'decoder, S> {
+    entries: MapDecoder<'decoder, S>,
+    keys: BTreeSet<Value>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, 'decoder, S: Source<'de>> MapAccess<'de> for UniqueMapDecoder<'decoder, S> {
+    type Error = DecodeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if !self.entries.entries.has_next()? {
+            return Ok(None);
+        }
+        let decoder = &mut *self.entries.entries.decoder;
+        let (key, found) = decoder.record_key(|decoder| seed.deserialize(decoder))?;
+        match self.keys.insert(found) {
+            true => Ok(Some(key)),
+            false => Err(DecodeError::DuplicateKey),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        self.entries.next_value_seed(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.entries.size_hint()
+    }
+}
+
+/// Gives access to the entries of a map read again, skipping the ones dropped
+/// because of a duplicate key, read [Decoder::visit_kept_map]
+#[cfg(feature = "alloc")]
+struct KeptMapDecoder<'decoder, S> {
+    entries: MapDecoder<'decoder, S>,
+    /// Whether each entry left is kept
+    kept: vec::IntoIter<bool>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, 'decoder, S: Source<'de>> MapAccess<'de> for KeptMapDecoder<'decoder, S> {
+    type Error = DecodeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        while self.entries.entries.has_next()? {
+            let decoder = &mut *self.entries.entries.decoder;
+            if self.kept.next().unwrap_or(true) {
+                return seed.deserialize(decoder).map(Some);
+            }
+            decoder.skip_item()?;
+            decoder.skip_item()?;
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        self.entries.next_value_seed(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.kept.as_slice().iter().filter(|kept| **kept).count())
+    }
+}
+
+/// Gives access to the number of a tag followed by its enclosed data item
+struct TagDecoder<'decoder, S> {
+    decoder: &'decoder mut Decoder<S>,
+    tag: u64,
+    /// Items left to read, first the tag number and then the enclosed data item
+    remaining: u8,
+}
+
+impl<'de, 'decoder, S: Source<'de>> SeqAccess<'de> for TagDecoder<'decoder, S> {
+    type Error = DecodeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let remaining = self.remaining;
+        self.remaining = remaining.saturating_sub(1);
+        match remaining {
+            2 => seed.deserialize(self.tag.into_deserializer()).map(Some),
+            1 => seed.deserialize(&mut *self.decoder).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining as usize)
+    }
+}
+
+/// Gives access to data items outside of the serde data model as an enum whose
+/// variant is named after the reserved name of its type, read [crate::value]
+#[cfg(feature = "alloc")]
+enum SpecialDecoder<'decoder, S> {
+    Tag(TagDecoder<'decoder, S>),
+    Simple(u8),
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, 'decoder, S: Source<'de>> EnumAccess<'de> for SpecialDecoder<'decoder, S> {
+    type Error = DecodeError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let name = match self {
+            SpecialDecoder::Tag(_) => TAG_NAME,
+            SpecialDecoder::Simple(_) => SIMPLE_NAME,
+        };
+        let variant = seed.deserialize(BorrowedStrDeserializer::<DecodeError>::new(name))?;
+        Ok((variant, self))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, 'decoder, S: Source<'de>> VariantAccess<'de> for SpecialDecoder<'decoder, S> {
+    type Error = DecodeError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Err(self.mismatch("unit variant"))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self {
+            SpecialDecoder::Simple(value) => seed.deserialize(value.into_deserializer()),
+            special => Err(special.mismatch("newtype variant")),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            SpecialDecoder::Tag(mut access) => {
+                let value = visitor.visit_seq(&mut access)?;
+                match access.remaining {
+                    0 => Ok(value),
+                    _ => Err(DecodeError::LengthMismatch),
+                }
+            }
+            special => Err(special.mismatch("tuple variant")),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(self.mismatch("struct variant"))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<S> SpecialDecoder<'_, S> {
+    fn mismatch(&self, expected: &'static str) -> DecodeError {
+        DecodeError::TypeMismatch {
+            expected,
+            found: match self {
+                SpecialDecoder::Tag(_) => "tag",
+                SpecialDecoder::Simple(_) => "simple value",
+            },
+        }
+    }
+}
+
+/// Gives access to an enum variant encoded as an array
+struct EnumDecoder<'decoder, S> {
+    decoder: &'decoder mut Decoder<S>,
+    /// Items left after the variant name, [None] for indefinite length arrays
+    remaining: Option<u64>,
+}
+
+impl<'de, 'decoder, S: Source<'de>> EnumDecoder<'decoder, S> {
+    /// Ensure the array holding the variant has exactly `count` items left
+    fn expect_remaining(&mut self, count: u64) -> Result<(), DecodeError> {
+        match self.remaining {
+            Some(remaining) if remaining == count => Ok(()),
+            None => Ok(()),
+            Some(_) => Err(DecodeError::LengthMismatch),
+        }
+    }
+
+    /// Ensure the array holding the variant has no items left
+    fn end(&mut self) -> Result<(), DecodeError> {
+        if self.remaining.is_some() || self.decoder.consume_break()? {
+            Ok(())
+        } else {
+            Err(DecodeError::LengthMismatch)
+        }
+    }
+}
+
+impl<'de, 'decoder, S: Source<'de>> EnumAccess<'de> for EnumDecoder<'decoder, S> {
+    type Error = DecodeError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(&mut *self.decoder)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'decoder, S: Source<'de>> VariantAccess<'de> for EnumDecoder<'decoder, S> {
+    type Error = DecodeError;
+
+    fn unit_variant(mut self) -> Result<(), Self::Error> {
+        self.expect_remaining(0)?;
+        self.end()
+    }
+
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.expect_remaining(1)?;
+        let value = seed.deserialize(&mut *self.decoder)?;
+        self.end()?;
+        Ok(value)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let mut access = SeqDecoder::new(self.decoder, self.remaining);
+        let value = visitor.visit_seq(&mut access)?;
+        access.end()?;
+        Ok(value)
+    }
+
+    fn struct_variant<V>(
+        mut self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.expect_remaining(1)?;
+        let value = self.decoder.deserialize_map(visitor)?;
+        self.end()?;
+        Ok(value)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::source::{IoSource, SliceSource};
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    fn decode<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, DecodeError> {
+        decode_with_options(bytes, DecodeOptions::default())
+    }
+
+    fn decode_with_options<'de, T: Deserialize<'de>>(
+        bytes: &'de [u8],
+        options: DecodeOptions,
+    ) -> Result<T, DecodeError> {
+        let mut decoder = Decoder::with_options(SliceSource::new(bytes), options);
+        let value = T::deserialize(&mut decoder)?;
+        decoder.end()?;
+        Ok(value)
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Polygon { sides: u8 },
+    }
+
+    #[test]
+    fn data_items() {
+        // ["Point", ["Circle", 1.5], ["Polygon", {"sides": 6}]]
+        let shapes = [
+            0x83, // array(3)
+            0x65, b'P', b'o', b'i', b'n', b't', // "Point"
+            0x82, 0x66, b'C', b'i', b'r', b'c', b'l', b'e', // ["Circle",
+            0xF9, 0x3E, 0x00, // 1.5]
+            0x82, 0x67, b'P', b'o', b'l', b'y', b'g', b'o', b'n', // ["Polygon",
+            0xA1, 0x65, b's', b'i', b'd', b'e', b's', 0x06, // {"sides": 6}]
+        ];
+        assert_eq!(
+            decode::<Vec<Shape>>(&shapes).unwrap(),
+            [
+                Shape::Point,
+                Shape::Circle(1.5),
+                Shape::Polygon { sides: 6 }
+            ]
+        );
+        assert_eq!(decode::<i32>(&[0x39, 0x01, 0x2B]).unwrap(), -300);
+        assert!(matches!(
+            decode::<u8>(&[0x19, 0x01, 0x00]),
+            Err(DecodeError::Deserialization(_))
+        ));
+        assert!(decode::<String>(&[0x01]).is_err());
+        assert!(matches!(
+            decode::<u8>(&[0x00, 0x00]),
+            Err(DecodeError::TrailingData)
+        ));
+    }
+
+    #[test]
+    fn indefinite_lengths() {
+        // [_ 1, [2, 3], {_ "a": 4}] followed by (_ "ab", "c")
+        let array = [
+            0x9F, 0x01, 0x82, 0x02, 0x03, 0xBF, 0x61, 0x61, 0x04, 0xFF, 0xFF,
+        ];
+        let value: (u8, Vec<u8>, BTreeMap<String, u8>) = decode(&array).unwrap();
+        assert_eq!(
+            value,
+            (
+                1,
+                Vec::from([2, 3]),
+                BTreeMap::from([(String::from("a"), 4)])
+            )
+        );
+        let text = [0x7F, 0x62, 0x61, 0x62, 0x61, 0x63, 0xFF];
+        assert_eq!(decode::<String>(&text).unwrap(), "abc");
+    }
+
+    #[test]
+    fn depth_limit() {
+        let options = DecodeOptions {
+            max_depth: 2,
+            ..Default::default()
+        };
+        let nested = [0x81, 0x81, 0x81, 0x00];
+        assert!(matches!(
+            decode_with_options::<Vec<Vec<Vec<u8>>>>(&nested, options),
+            Err(DecodeError::DepthLimitExceeded)
+        ));
+        assert!(decode::<Vec<Vec<Vec<u8>>>>(&nested).is_ok());
+    }
+
+    #[test]
+    fn validity() {
+        let options = DecodeOptions {
+            check_validity: true,
+            ..Default::default()
+        };
+        // [0, "\xFF"], the text string is skipped by a tuple of one item
+        let invalid_text = b"\x82\x00\x61\xFF";
+        assert!(decode::<(u8, serde::de::IgnoredAny)>(invalid_text).is_ok());
+        assert!(matches!(
+            decode_with_options::<(u8, serde::de::IgnoredAny)>(invalid_text, options),
+            Err(DecodeError::InvalidUtf8)
+        ));
+        // (_ "\xC3", "\xA9"), a character split between chunks
+        let split = b"\x7F\x61\xC3\x61\xA9\xFF";
+        assert!(decode::<String>(split).is_ok());
+        assert!(matches!(
+            decode_with_options::<Value>(split, options),
+            Err(DecodeError::InvalidUtf8)
+        ));
+        // 0("2013-03-21T20:04:00Z") is fine, 0(0) and 2("1") are not
+        let date = b"\xC0\x74\x32\x30\x31\x33-03-21T20:04:00Z";
+        assert!(decode_with_options::<Value>(date, options).is_ok());
+        assert!(matches!(
+            decode_with_options::<Value>(b"\xC0\x00", options),
+            Err(DecodeError::InvalidTagContent(0))
+        ));
+        assert!(matches!(
+            decode_with_options::<u128>(b"\xC2\x61\x31", options),
+            Err(DecodeError::InvalidTagContent(2))
+        ));
+        // {"id": 1, "id": 2}, also written with a non preferred encoding of the second key
+        for bytes in [
+            &b"\xA2\x62id\x01\x62id\x02"[..],
+            b"\xA2\x62id\x01\x78\x02id\x02",
+        ] {
+            assert!(decode::<Value>(bytes).is_ok());
+            assert!(matches!(
+                decode_with_options::<BTreeMap<String, u8>>(bytes, options),
+                Err(DecodeError::DuplicateKey)
+            ));
+        }
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        #[derive(Deserialize)]
+        struct Signed {
+            amount: u8,
+            to: String,
+        }
+        // {"amount": 1, "to": "a", "amount": 2}
+        let bytes = b"\xA3\x66amount\x01\x62to\x61a\x66amount\x02";
+        // Unchecked leaves it to the type: derived structs fail, maps keep the last entry
+        assert!(decode::<Signed>(bytes).is_err());
+        assert_eq!(
+            decode::<BTreeMap<String, Value>>(bytes).unwrap()["amount"],
+            Value::Integer(2)
+        );
+        let options = DecodeOptions {
+            duplicate_keys: DuplicateKeys::Reject,
+            ..Default::default()
+        };
+        assert!(matches!(
+            decode_with_options::<Signed>(bytes, options),
+            Err(DecodeError::DuplicateKey)
+        ));
+        // Entries left keep their place, the kept one where it was found
+        for (duplicate_keys, amount, entries) in [
+            (
+                DuplicateKeys::KeepFirst,
+                1,
+                [("amount", Value::Integer(1)), ("to", Value::from("a"))],
+            ),
+            (
+                DuplicateKeys::KeepLast,
+                2,
+                [("to", Value::from("a")), ("amount", Value::Integer(2))],
+            ),
+        ] {
+            let options = DecodeOptions {
+                duplicate_keys,
+                ..Default::default()
+            };
+            let signed: Signed = decode_with_options(bytes, options).unwrap();
+            assert_eq!((signed.amount, signed.to.as_str()), (amount, "a"));
+            let Ok(Value::Map(found)) = decode_with_options(bytes, options) else {
+                panic!("a map is expected");
+            };
+            let entries = entries.map(|(key, value)| (Value::from(key), value));
+            assert_eq!(found, entries);
+        }
+        // A policy takes precedence over check_validity
+        let options = DecodeOptions {
+            check_validity: true,
+            duplicate_keys: DuplicateKeys::KeepLast,
+            ..Default::default()
+        };
+        let signed: Signed = decode_with_options(bytes, options).unwrap();
+        assert_eq!(signed.amount, 2);
+
+        // {_ "a": "x", "b": "y", "a": "z"}
+        let bytes = b"\xBF\x61a\x61x\x61b\x61y\x61a\x61z\xFF";
+        let options = DecodeOptions {
+            duplicate_keys: DuplicateKeys::Reject,
+            ..Default::default()
+        };
+        assert!(matches!(
+            decode_with_options::<BTreeMap<&str, &str>>(bytes, options),
+            Err(DecodeError::DuplicateKey)
+        ));
+        // Strings are still borrowed from the input, also when the map is read again
+        for (duplicate_keys, kept) in [
+            (DuplicateKeys::KeepFirst, "x"),
+            (DuplicateKeys::KeepLast, "z"),
+        ] {
+            let options = DecodeOptions {
+                duplicate_keys,
+                ..Default::default()
+            };
+            let map: BTreeMap<&str, &str> = decode_with_options(bytes, options).unwrap();
+            assert_eq!(map, BTreeMap::from([("a", kept), ("b", "y")]));
+            let mut decoder = Decoder::with_options(IoSource::new(&bytes[..]), options);
+            let map = BTreeMap::<String, String>::deserialize(&mut decoder).unwrap();
+            assert_eq!(map["a"], kept);
+        }
+        // {[{_ 0: 0}]: 1, [{0: 0}]: 2}, keys holding maps are recorded within the outer key
+        let bytes = b"\xA2\x81\xBF\x00\x00\xFF\x01\x81\xA1\x00\x00\x02";
+        let options = DecodeOptions {
+            duplicate_keys: DuplicateKeys::Reject,
+            ..Default::default()
+        };
+        let mut decoder = Decoder::with_options(IoSource::new(&bytes[..]), options);
+        assert!(matches!(
+            Value::deserialize(&mut decoder),
+            Err(DecodeError::DuplicateKey)
+        ));
     }
 }
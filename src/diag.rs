@@ -0,0 +1,440 @@
+//! Diagnostic notation (RFC 8949 section 8)
+//!
+//! A human readable rendering of CBOR akin to JSON, e.g.
+//! `{1: h'0102', "a": [1, 2.5, 0("2013-03-21T20:04:00Z")]}`, which [to_diag] produces from
+//! encoded bytes and [Value] from its [Display](fmt::Display) implementation
+//!
+//! Encoded bytes show how they were written as well: indefinite length items are marked
+//! with an underscore (`[_ 1, 2]`, `(_ h'01', h'02')`) and heads or floats using more
+//! bytes than needed carry an encoding indicator, `_0` to `_3` for additional
+//! information 24 to 27 (e.g. `1_0` for 0x1801, `1.5_3` for a double precision 1.5)
+//!
+//! [from_diag] goes the other way, turning extended diagnostic notation (EDN) back
+//! into the exact bytes it describes
+
+use crate::value::Value;
+#[cfg(feature = "de")]
+use crate::{
+    de::DecodeOptions,
+    error::DecodeError,
+    float::{f16_to_f64, float_width},
+    head::Head,
+};
+use alloc::string::{String, ToString};
+use core::fmt;
+
+mod parser;
+
+pub use parser::from_diag;
+
+/// Render the data items in `bytes` (a single one or a CBOR sequence, separated
+/// by commas) in diagnostic notation
+/// # Considerations
+/// - Text strings have to be valid UTF-8, there is no notation for them otherwise
+/// - Nesting is limited to the default [DecodeOptions::max_depth], read
+///   [to_diag_with_options] to change it
+#[cfg(feature = "de")]
+pub fn to_diag(bytes: &[u8]) -> Result<String, DecodeError> {
+    to_diag_with_options(bytes, DecodeOptions::default())
+}
+
+/// Same as [to_diag] but with the given [DecodeOptions], only
+/// [DecodeOptions::max_depth] applies
+#[cfg(feature = "de")]
+pub fn to_diag_with_options(bytes: &[u8], options: DecodeOptions) -> Result<String, DecodeError> {
+    let mut diag = Diag {
+        bytes,
+        position: 0,
+        depth: 0,
+        max_depth: options.max_depth,
+        output: String::new(),
+    };
+    while diag.position < bytes.len() {
+        if diag.position > 0 {
+            diag.output.push_str(", ");
+        }
+        diag.item()?;
+    }
+    Ok(diag.output)
+}
+
+#[cfg(feature = "de")]
+struct Diag<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    depth: usize,
+    max_depth: usize,
+    output: String,
+}
+
+#[cfg(feature = "de")]
+impl<'a> Diag<'a> {
+    fn head(&mut self) -> Result<Head, DecodeError> {
+        let head = Head::read(&self.bytes[self.position..])?;
+        self.position += head.size;
+        Ok(head)
+    }
+
+    fn take(&mut self, length: u64) -> Result<&'a [u8], DecodeError> {
+        let length = usize::try_from(length).map_err(|_| DecodeError::LengthOutOfBounds)?;
+        let bytes = self
+            .bytes
+            .get(self.position..)
+            .and_then(|rest| rest.get(..length))
+            .ok_or(DecodeError::UnexpectedEof)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    /// Consume the break byte closing an indefinite length item, returns whether it was found
+    fn consume_break(&mut self) -> Result<bool, DecodeError> {
+        // 0xFF = break byte
+        match self.bytes.get(self.position) {
+            Some(0xFF) => {
+                self.position += 1;
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err(DecodeError::UnexpectedEof),
+        }
+    }
+
+    fn recurse(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<(), DecodeError>,
+    ) -> Result<(), DecodeError> {
+        if self.depth >= self.max_depth {
+            return Err(DecodeError::DepthLimitExceeded);
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn indicator(&mut self, head: &Head) {
+        if !head.is_shortest() {
+            write_indicator(&mut self.output, head.additional_information());
+        }
+    }
+
+    fn item(&mut self) -> Result<(), DecodeError> {
+        let head = self.head()?;
+        match (head.major_type(), head.argument) {
+            (0, Some(value)) => self.output.push_str(&value.to_string()),
+            (1, Some(value)) => self.output.push_str(&(-1 - value as i128).to_string()),
+            (2 | 3, Some(length)) => self.string(head.major_type(), length)?,
+            (2 | 3, None) => self.chunks(head.major_type())?,
+            (4 | 5, _) => return self.recurse(|diag| diag.collection(&head)),
+            (6, Some(tag)) => {
+                self.output.push_str(&tag.to_string());
+                self.indicator(&head);
+                self.output.push('(');
+                self.recurse(Self::item)?;
+                self.output.push(')');
+                return Ok(());
+            }
+            _ => return self.simple_or_float(&head),
+        }
+        self.indicator(&head);
+        Ok(())
+    }
+
+    fn string(&mut self, major_type: u8, length: u64) -> Result<(), DecodeError> {
+        let bytes = self.take(length)?;
+        match major_type {
+            2 => write_bytes(&mut self.output, bytes),
+            _ => write_text(
+                &mut self.output,
+                core::str::from_utf8(bytes).map_err(|_| DecodeError::InvalidUtf8)?,
+            ),
+        }
+        Ok(())
+    }
+
+    /// The chunks of an indefinite length string, `''_` or `""_` if there are none
+    fn chunks(&mut self, major_type: u8) -> Result<(), DecodeError> {
+        if self.consume_break()? {
+            self.output
+                .push_str(if major_type == 2 { "''_" } else { "\"\"_" });
+            return Ok(());
+        }
+        self.output.push_str("(_ ");
+        loop {
+            let head = self.head()?;
+            match (head.major_type(), head.argument) {
+                (chunk_type, Some(length)) if chunk_type == major_type => {
+                    self.string(major_type, length)?;
+                    self.indicator(&head);
+                }
+                _ => return Err(DecodeError::InvalidChunk),
+            }
+            if self.consume_break()? {
+                break;
+            }
+            self.output.push_str(", ");
+        }
+        self.output.push(')');
+        Ok(())
+    }
+
+    fn collection(&mut self, head: &Head) -> Result<(), DecodeError> {
+        let is_map = head.major_type() == 5;
+        self.output.push(if is_map { '{' } else { '[' });
+        match head.argument {
+            None => self.output.push_str("_ "),
+            Some(_) if !head.is_shortest() => {
+                self.indicator(head);
+                self.output.push(' ');
+            }
+            Some(_) => {}
+        }
+        let mut index = 0;
+        loop {
+            let done = match head.argument {
+                Some(length) => index == length,
+                None => self.consume_break()?,
+            };
+            if done {
+                break;
+            }
+            if index > 0 {
+                self.output.push_str(", ");
+            }
+            self.item()?;
+            if is_map {
+                self.output.push_str(": ");
+                self.item()?;
+            }
+            index += 1;
+        }
+        self.output.push(if is_map { '}' } else { ']' });
+        Ok(())
+    }
+
+    fn simple_or_float(&mut self, head: &Head) -> Result<(), DecodeError> {
+        let (additional_information, argument) =
+            (head.additional_information(), head.argument.unwrap_or(0));
+        let value = match additional_information {
+            // 0xF9 = half precision float
+            25 => f16_to_f64(argument as u16),
+            // 0xFA = single precision float
+            26 => f32::from_bits(argument as u32) as f64,
+            // 0xFB = double precision float
+            27 => f64::from_bits(argument),
+            // 0xFF = break byte
+            31 => return Err(DecodeError::UnexpectedBreak),
+            _ => {
+                write_simple(&mut self.output, argument as u8);
+                return Ok(());
+            }
+        };
+        write_float(&mut self.output, value);
+        if float_width(value) < additional_information {
+            write_indicator(&mut self.output, additional_information);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "de")]
+fn write_indicator(output: &mut String, additional_information: u8) {
+    output.push('_');
+    output.push_str(&(additional_information - 24).to_string());
+}
+
+fn write_bytes(output: &mut String, bytes: &[u8]) {
+    output.push_str("h'");
+    for byte in bytes {
+        output.push(char::from_digit((byte >> 4) as u32, 16).expect("a nibble is a hex digit"));
+        output.push(char::from_digit((byte & 0x0F) as u32, 16).expect("a nibble is a hex digit"));
+    }
+    output.push('\'');
+}
+
+/// Text strings are written as JSON strings
+fn write_text(output: &mut String, text: &str) {
+    output.push('"');
+    for char in text.chars() {
+        match char {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            char if char.is_control() => {
+                output.push_str("\\u");
+                let code = char as u32;
+                for shift in [12, 8, 4, 0] {
+                    output.push(
+                        char::from_digit((code >> shift) & 0x0F, 16)
+                            .expect("a nibble is a hex digit"),
+                    );
+                }
+            }
+            char => output.push(char),
+        }
+    }
+    output.push('"');
+}
+
+fn write_simple(output: &mut String, value: u8) {
+    match value {
+        20 => output.push_str("false"),
+        21 => output.push_str("true"),
+        22 => output.push_str("null"),
+        23 => output.push_str("undefined"),
+        _ => {
+            output.push_str("simple(");
+            output.push_str(&value.to_string());
+            output.push(')');
+        }
+    }
+}
+
+fn write_float(output: &mut String, value: f64) {
+    match value {
+        _ if value.is_nan() => output.push_str("NaN"),
+        f64::INFINITY => output.push_str("Infinity"),
+        f64::NEG_INFINITY => output.push_str("-Infinity"),
+        // Debug always keeps a fractional part or an exponent, telling floats from integers
+        _ => output.push_str(&alloc::format!("{value:?}")),
+    }
+}
+
+fn write_value(output: &mut String, value: &Value) {
+    match value {
+        Value::Integer(value) => output.push_str(&value.to_string()),
+        Value::Bytes(bytes) => write_bytes(output, bytes),
+        Value::Text(text) => write_text(output, text),
+        Value::Array(items) => {
+            output.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    output.push_str(", ");
+                }
+                write_value(output, item);
+            }
+            output.push(']');
+        }
+        Value::Map(entries) => {
+            output.push('{');
+            for (index, (key, value)) in entries.iter().enumerate() {
+                if index > 0 {
+                    output.push_str(", ");
+                }
+                write_value(output, key);
+                output.push_str(": ");
+                write_value(output, value);
+            }
+            output.push('}');
+        }
+        Value::Tag(tag, value) => {
+            output.push_str(&tag.to_string());
+            output.push('(');
+            write_value(output, value);
+            output.push(')');
+        }
+        Value::Simple(value) => write_simple(output, *value),
+        Value::Float(value) => write_float(output, *value),
+        Value::Bool(value) => write_simple(output, 20 + *value as u8),
+        Value::Null => write_simple(output, 22),
+        Value::Undefined => write_simple(output, 23),
+    }
+}
+
+/// Diagnostic notation of the data item, read [crate::diag]
+/// # Considerations
+/// - Integers out of the 64 bit range are written as numbers rather than as bignums
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut output = String::new();
+        write_value(&mut output, self);
+        f.write_str(&output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor;
+
+    #[cfg(feature = "de")]
+    #[test]
+    fn diag() {
+        let cases: [(&[u8], &str); 12] = [
+            (
+                b"\xA2\x01\x42\x01\x02\x61a\x83\x01\xF9\x41\x00\xC0\x74\
+                  2013-03-21T20:04:00Z",
+                r#"{1: h'0102', "a": [1, 2.5, 0("2013-03-21T20:04:00Z")]}"#,
+            ),
+            (
+                b"\x9F\x01\x82\x02\x03\x9F\x04\x05\xFF\xFF",
+                "[_ 1, [2, 3], [_ 4, 5]]",
+            ),
+            (b"\xBF\x61a\x01\xFF", r#"{_ "a": 1}"#),
+            (
+                b"\x5F\x42\x01\x02\x43\x03\x04\x05\xFF",
+                "(_ h'0102', h'030405')",
+            ),
+            (b"\x7F\xFF", "\"\"_"),
+            (b"\x62\x22\x0A", r#""\"\n""#),
+            (b"\x18\x17\x98\x01\x01\xD8\x01\x00", "23_0, [_0 1], 1_0(0)"),
+            (
+                b"\x3B\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+                "-18446744073709551616",
+            ),
+            (
+                b"\xFB\x3F\xF8\0\0\0\0\0\0\xFA\x47\xC3\x50\x00",
+                "1.5_3, 100000.0",
+            ),
+            (b"\xF9\x7C\x00\xFA\x7F\xC0\x00\x00", "Infinity, NaN_2"),
+            (
+                b"\xF4\xF6\xF7\xF0\xF8\xFF",
+                "false, null, undefined, simple(16), simple(255)",
+            ),
+            (b"", ""),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(to_diag(bytes).unwrap(), expected);
+        }
+        assert!(matches!(
+            to_diag(b"\x82\x01"),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            to_diag(b"\x81\xFF"),
+            Err(DecodeError::UnexpectedBreak)
+        ));
+        assert!(matches!(
+            to_diag(b"\x5F\x61a\xFF"),
+            Err(DecodeError::InvalidChunk)
+        ));
+        assert!(matches!(
+            to_diag(b"\x61\xFF"),
+            Err(DecodeError::InvalidUtf8)
+        ));
+        let options = DecodeOptions {
+            max_depth: 1,
+            ..Default::default()
+        };
+        assert_eq!(to_diag_with_options(b"\x81\x00", options).unwrap(), "[0]");
+        assert!(matches!(
+            to_diag_with_options(b"\x81\x81\x00", options),
+            Err(DecodeError::DepthLimitExceeded)
+        ));
+    }
+
+    #[test]
+    fn display() {
+        let value = cbor!({
+            1 => b"\x01\x02",
+            "a" => [1, 2.5, tag(0, "2013-03-21T20:04:00Z")],
+            simple(16) => [null, undefined, true, -1.0, f64::NAN],
+        });
+        assert_eq!(
+            value.to_string(),
+            r#"{1: h'0102', "a": [1, 2.5, 0("2013-03-21T20:04:00Z")], simple(16): [null, undefined, true, -1.0, NaN]}"#
+        );
+    }
+}
@@ -0,0 +1,580 @@
+//! Parsing extended diagnostic notation (EDN) into encoded bytes
+
+use crate::{
+    codec::{decode_base64, decode_hex},
+    error::DiagError,
+    float::{f64_to_f16, float_width},
+};
+use alloc::{string::String, vec::Vec};
+
+/// Nesting depth of arrays, maps, tags and embedded items the parser accepts
+const MAX_DEPTH: usize = 128;
+
+/// Encode the data items written in extended diagnostic notation in `text` (a single one
+/// or a CBOR sequence, separated by commas), byte for byte as the notation describes them
+///
+/// Besides what [to_diag](crate::diag::to_diag) writes, the parser accepts:
+/// - Integers in hexadecimal (`0x1F`), octal (`0o17`) or binary (`0b101`), integers out
+///   of the 64 bit range become bignums
+/// - `'text'` for a byte string holding that text, `b64'...'` for a byte string in base64
+///   (either alphabet, padding optional) and `<<item, ...>>` for a byte string holding
+///   the encoded items
+/// - Comments between slashes (`/ comment /`) or from `#` to the end of the line
+///
+/// Items without an encoding indicator get their preferred serialization, the shortest head
+/// for their argument and the shortest float holding their value exactly
+/// ```
+/// use serde_cbored::diag::from_diag;
+///
+/// let bytes = from_diag("[_ 1_1, h'0A' / comment /, 1.5]").unwrap();
+/// assert_eq!(bytes, [0x9F, 0x19, 0x00, 0x01, 0x41, 0x0A, 0xF9, 0x3E, 0x00, 0xFF]);
+/// ```
+pub fn from_diag(text: &str) -> Result<Vec<u8>, DiagError> {
+    let mut parser = Parser {
+        text,
+        position: 0,
+        depth: 0,
+        output: Vec::new(),
+    };
+    parser.sequence(None)?;
+    match parser.peek() {
+        Some(_) => Err(DiagError::UnexpectedCharacter(parser.position)),
+        None => Ok(parser.output),
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    depth: usize,
+    output: Vec<u8>,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    /// Next character that is not whitespace nor part of a comment, without consuming it
+    fn peek(&mut self) -> Option<char> {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+            self.position += match trimmed.chars().next()? {
+                '/' => match trimmed[1..].find('/') {
+                    Some(end) => end + 2,
+                    // An unterminated comment is reported where it starts
+                    None => return Some('/'),
+                },
+                '#' => trimmed.find('\n').unwrap_or(trimmed.len()),
+                char => return Some(char),
+            };
+        }
+    }
+
+    fn next_char(&mut self) -> Result<char, DiagError> {
+        let char = self.rest().chars().next().ok_or(DiagError::UnexpectedEnd)?;
+        self.position += char.len_utf8();
+        Ok(char)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), DiagError> {
+        match self.peek() {
+            Some(char) if char == expected => {
+                self.position += char.len_utf8();
+                Ok(())
+            }
+            Some(_) => Err(DiagError::UnexpectedCharacter(self.position)),
+            None => Err(DiagError::UnexpectedEnd),
+        }
+    }
+
+    /// Consume `prefix` if the text continues with it, comments are not skipped
+    fn eat(&mut self, prefix: &str) -> bool {
+        let found = self.rest().starts_with(prefix);
+        if found {
+            self.position += prefix.len();
+        }
+        found
+    }
+
+    /// An encoding indicator right after an item, `_0` to `_3`
+    fn indicator(&mut self) -> Result<Option<u8>, DiagError> {
+        let start = self.position;
+        if !self.rest().starts_with('_')
+            || !self.rest()[1..].starts_with(|c: char| c.is_ascii_digit())
+        {
+            return Ok(None);
+        }
+        self.position += 2;
+        match self.text.as_bytes()[start + 1] {
+            digit @ b'0'..=b'3'
+                if !self.rest().starts_with(|c: char| c.is_ascii_alphanumeric()) =>
+            {
+                Ok(Some(digit - b'0'))
+            }
+            _ => Err(DiagError::InvalidIndicator(start)),
+        }
+    }
+
+    fn recurse(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<(), DiagError>,
+    ) -> Result<(), DiagError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(DiagError::DepthLimitExceeded(self.position));
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Write a head with the shortest argument encoding or the one given by `indicator`
+    fn head(
+        &mut self,
+        major_type: u8,
+        argument: u64,
+        indicator: Option<u8>,
+        offset: usize,
+    ) -> Result<(), DiagError> {
+        let shortest = match argument {
+            0..24 => None,
+            24..=0xFF => Some(0),
+            0x100..=0xFFFF => Some(1),
+            0x1_0000..=0xFFFF_FFFF => Some(2),
+            _ => Some(3),
+        };
+        match indicator.or(shortest) {
+            None => self.output.push(major_type << 5 | argument as u8),
+            Some(size) if shortest.is_none_or(|shortest| size >= shortest) => {
+                self.output.push(major_type << 5 | (24 + size));
+                self.output
+                    .extend_from_slice(&argument.to_be_bytes()[8 - (1 << size)..]);
+            }
+            Some(_) => return Err(DiagError::InvalidIndicator(offset)),
+        }
+        Ok(())
+    }
+
+    /// Items separated by commas until `end` (or the end of the text), a trailing comma is allowed
+    fn sequence(&mut self, end: Option<char>) -> Result<(), DiagError> {
+        while self.peek().is_some() && self.peek() != end {
+            self.item()?;
+            if self.peek() != Some(',') {
+                break;
+            }
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    fn item(&mut self) -> Result<(), DiagError> {
+        match self.peek().ok_or(DiagError::UnexpectedEnd)? {
+            '[' | '{' => self.recurse(Self::collection),
+            '(' => self.chunks(),
+            '"' | '\'' | '<' => self.string(),
+            '0'..='9' | '-' => self.number(),
+            char if char.is_ascii_alphabetic() => self.word(),
+            _ => Err(DiagError::UnexpectedCharacter(self.position)),
+        }
+    }
+
+    /// Arrays and maps, the head is written once the items have been counted
+    fn collection(&mut self) -> Result<(), DiagError> {
+        let start = self.position;
+        let (major_type, end) = match self.next_char()? {
+            '[' => (4, ']'),
+            _ => (5, '}'),
+        };
+        let indicator = self.indicator()?;
+        let indefinite = indicator.is_none() && self.eat("_");
+        let items = self.output.len();
+        let mut count = 0;
+        while self.peek() != Some(end) {
+            self.item()?;
+            if major_type == 5 {
+                self.expect(':')?;
+                self.item()?;
+            }
+            count += 1;
+            if self.peek() != Some(',') {
+                break;
+            }
+            self.position += 1;
+        }
+        self.expect(end)?;
+        let items = self.output.split_off(items);
+        if indefinite {
+            self.output.push(major_type << 5 | 31);
+            self.output.extend_from_slice(&items);
+            // 0xFF = break byte
+            self.output.push(0xFF);
+        } else {
+            self.head(major_type, count, indicator, start)?;
+            self.output.extend_from_slice(&items);
+        }
+        Ok(())
+    }
+
+    /// Indefinite length strings, `(_ chunk, ...)`
+    fn chunks(&mut self) -> Result<(), DiagError> {
+        let start = self.position;
+        self.position += 1;
+        if !self.eat("_") {
+            return Err(DiagError::UnexpectedCharacter(start + 1));
+        }
+        let mut major_type = None;
+        let header = self.output.len();
+        self.output.push(0);
+        loop {
+            self.peek();
+            let chunk = self.position;
+            let (chunk_type, content) = self.string_content()?;
+            if *major_type.get_or_insert(chunk_type) != chunk_type {
+                return Err(DiagError::InvalidChunk(chunk));
+            }
+            let indicator = self.indicator()?;
+            self.head(chunk_type, content.len() as u64, indicator, chunk)?;
+            self.output.extend_from_slice(&content);
+            if self.peek() != Some(',') {
+                break;
+            }
+            self.position += 1;
+        }
+        self.expect(')')?;
+        self.output[header] = major_type.expect("there is at least one chunk") << 5 | 31;
+        // 0xFF = break byte
+        self.output.push(0xFF);
+        Ok(())
+    }
+
+    /// A definite length string, or an empty indefinite length one when followed by `_`
+    fn string(&mut self) -> Result<(), DiagError> {
+        let start = self.position;
+        let (major_type, content) = self.string_content()?;
+        let indicator = self.indicator()?;
+        if indicator.is_none() && self.eat("_") {
+            if !content.is_empty() {
+                return Err(DiagError::InvalidIndicator(self.position - 1));
+            }
+            // 0xFF = break byte
+            self.output.extend_from_slice(&[major_type << 5 | 31, 0xFF]);
+            return Ok(());
+        }
+        self.head(major_type, content.len() as u64, indicator, start)?;
+        self.output.extend_from_slice(&content);
+        Ok(())
+    }
+
+    /// Major type and content of any kind of string literal
+    fn string_content(&mut self) -> Result<(u8, Vec<u8>), DiagError> {
+        let start = self.position;
+        match self.peek().ok_or(DiagError::UnexpectedEnd)? {
+            '"' => Ok((3, self.quoted('"')?.into_bytes())),
+            '\'' => Ok((2, self.quoted('\'')?.into_bytes())),
+            '<' if self.eat("<<") => {
+                let items = self.output.len();
+                self.recurse(|parser| parser.sequence(Some('>')))?;
+                if !self.eat(">>") {
+                    return Err(DiagError::UnexpectedCharacter(self.position));
+                }
+                Ok((2, self.output.split_off(items)))
+            }
+            _ if self.eat("h'") => self.encoded(start, decode_hex).map(|bytes| (2, bytes)),
+            _ if self.eat("b64'") => self.encoded(start, decode_base64).map(|bytes| (2, bytes)),
+            _ => Err(DiagError::UnexpectedCharacter(start)),
+        }
+    }
+
+    /// Content of a string between `quote`s, with JSON escapes
+    fn quoted(&mut self, quote: char) -> Result<String, DiagError> {
+        self.position += 1;
+        let mut content = String::new();
+        loop {
+            let start = self.position;
+            match self.next_char()? {
+                char if char == quote => return Ok(content),
+                '\\' => {
+                    let escaped = match self.next_char()? {
+                        'b' => '\u{8}',
+                        'f' => '\u{C}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.unicode_escape(start)?,
+                        char @ ('"' | '\'' | '\\' | '/') => char,
+                        _ => return Err(DiagError::InvalidString(start)),
+                    };
+                    content.push(escaped);
+                }
+                char => content.push(char),
+            }
+        }
+    }
+
+    /// The character of a `\u` escape, surrogate pairs taking two escapes
+    fn unicode_escape(&mut self, start: usize) -> Result<char, DiagError> {
+        let high = self.code_unit(start)?;
+        let low = match high {
+            0xD800..0xDC00 if self.eat("\\u") => Some(self.code_unit(start)?),
+            _ => None,
+        };
+        char::decode_utf16(core::iter::once(high).chain(low))
+            .next()
+            .and_then(Result::ok)
+            .ok_or(DiagError::InvalidString(start))
+    }
+
+    fn code_unit(&mut self, start: usize) -> Result<u16, DiagError> {
+        // from_str_radix would take a sign
+        let digits = self
+            .rest()
+            .get(..4)
+            .filter(|digits| digits.bytes().all(|digit| digit.is_ascii_hexdigit()))
+            .ok_or(DiagError::InvalidString(start))?;
+        self.position += 4;
+        u16::from_str_radix(digits, 16).map_err(|_| DiagError::InvalidString(start))
+    }
+
+    /// Content of a `h'...'` or `b64'...'` string, whitespace is allowed anywhere inside
+    fn encoded(
+        &mut self,
+        start: usize,
+        decode: fn(&[u8]) -> Option<Vec<u8>>,
+    ) -> Result<Vec<u8>, DiagError> {
+        let end = self.rest().find('\'').ok_or(DiagError::UnexpectedEnd)?;
+        let digits: Vec<u8> = self.rest()[..end]
+            .bytes()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .collect();
+        self.position += end + 1;
+        decode(&digits).ok_or(DiagError::InvalidString(start))
+    }
+
+    fn number(&mut self) -> Result<(), DiagError> {
+        let start = self.position;
+        let negative = self.eat("-");
+        if self.eat("Infinity") {
+            return self.float(f64::NEG_INFINITY, start);
+        }
+        let length = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-')))
+            .unwrap_or(self.rest().len());
+        let token = &self.text[start..self.position + length];
+        let digits = &self.rest()[..length];
+        self.position += length;
+        let radix = match digits.get(..2) {
+            Some("0x" | "0X") => 16,
+            Some("0o" | "0O") => 8,
+            Some("0b" | "0B") => 2,
+            _ => 10,
+        };
+        if radix == 10 && digits.contains(['.', 'e', 'E']) {
+            let value = token.parse().map_err(|_| DiagError::InvalidNumber(start))?;
+            return self.float(value, start);
+        }
+        let digits = if radix == 10 { digits } else { &digits[2..] };
+        let magnitude = u128::from_str_radix(digits, radix)
+            .ok()
+            .filter(|_| !digits.starts_with('+'))
+            .ok_or(DiagError::InvalidNumber(start))?;
+        let indicator = self.indicator()?;
+        if self.rest().starts_with('(') {
+            // A tag number
+            let tag = u64::try_from(magnitude)
+                .ok()
+                .filter(|_| !negative)
+                .ok_or(DiagError::InvalidNumber(start))?;
+            self.head(6, tag, indicator, start)?;
+            self.position += 1;
+            self.recurse(Self::item)?;
+            return self.expect(')');
+        }
+        let (major_type, argument) = match (negative, magnitude) {
+            (false, _) => (0, magnitude),
+            (true, 0) => (0, 0),
+            (true, _) => (1, magnitude - 1),
+        };
+        match u64::try_from(argument) {
+            Ok(argument) => self.head(major_type, argument, indicator, start),
+            Err(_) if indicator.is_some() => Err(DiagError::InvalidIndicator(start)),
+            Err(_) => {
+                // 0xC2 = tag(2), unsigned bignum, 0xC3 = tag(3), negative bignum
+                self.output.push(0xC2 | major_type);
+                let bytes = argument.to_be_bytes();
+                let bytes = &bytes[(argument.leading_zeros() / 8) as usize..];
+                self.head(2, bytes.len() as u64, None, start)?;
+                self.output.extend_from_slice(bytes);
+                Ok(())
+            }
+        }
+    }
+
+    fn float(&mut self, value: f64, start: usize) -> Result<(), DiagError> {
+        let width = match self.indicator()? {
+            None => float_width(value),
+            Some(size @ 1..=3) if 24 + size >= float_width(value) => 24 + size,
+            Some(_) => return Err(DiagError::InvalidIndicator(start)),
+        };
+        match width {
+            // 0xF9 = half precision float
+            25 => {
+                let bits = f64_to_f16(value).expect("the value fits in half precision");
+                self.output.push(0xF9);
+                self.output.extend_from_slice(&bits.to_be_bytes());
+            }
+            // 0xFA = single precision float
+            26 => {
+                self.output.push(0xFA);
+                self.output
+                    .extend_from_slice(&(value as f32).to_bits().to_be_bytes());
+            }
+            // 0xFB = double precision float
+            _ => {
+                self.output.push(0xFB);
+                self.output
+                    .extend_from_slice(&value.to_bits().to_be_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    /// Keywords, `simple(n)` and the prefixed strings (`h'...'`, `b64'...'`)
+    fn word(&mut self) -> Result<(), DiagError> {
+        let start = self.position;
+        let length = self
+            .rest()
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(self.rest().len());
+        let word = &self.rest()[..length];
+        if self.rest()[length..].starts_with('\'') {
+            return self.string();
+        }
+        self.position += length;
+        match word {
+            // 0xF4 = false, 0xF5 = true, 0xF6 = null, 0xF7 = undefined
+            "false" => self.output.push(0xF4),
+            "true" => self.output.push(0xF5),
+            "null" => self.output.push(0xF6),
+            "undefined" => self.output.push(0xF7),
+            "NaN" => return self.float(f64::NAN, start),
+            "Infinity" => return self.float(f64::INFINITY, start),
+            "simple" => {
+                self.expect('(')?;
+                self.peek();
+                let digits = self.position;
+                let length = self
+                    .rest()
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(self.rest().len());
+                let value = self.rest()[..length]
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|value| !(24..32).contains(value))
+                    .ok_or(DiagError::InvalidNumber(digits))?;
+                self.position += length;
+                self.expect(')')?;
+                // 0xF8 = simple value in the next byte, values below 24 do not need it
+                match value {
+                    0..24 => self.output.push(0xE0 | value),
+                    _ => self.output.extend_from_slice(&[0xF8, value]),
+                }
+            }
+            _ => return Err(DiagError::UnexpectedCharacter(start)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let cases: [(&str, &[u8]); 15] = [
+            ("0, 23_0, -1, -0", b"\x00\x18\x17\x20\x00"),
+            ("0x1F, 0o17, 0b101", b"\x18\x1F\x0F\x05"),
+            (
+                "18446744073709551616, -18446744073709551617",
+                b"\xC2\x49\x01\0\0\0\0\0\0\0\0\xC3\x49\x01\0\0\0\0\0\0\0\0",
+            ),
+            (
+                "1.5, 100000.0, -0.0, 1.5_3",
+                b"\xF9\x3E\x00\xFA\x47\xC3\x50\x00\xF9\x80\x00\xFB\x3F\xF8\0\0\0\0\0\0",
+            ),
+            ("1.1", b"\xFB\x3F\xF1\x99\x99\x99\x99\x99\x9A"),
+            (
+                "NaN, Infinity, -Infinity_2",
+                b"\xF9\x7E\x00\xF9\x7C\x00\xFA\xFF\x80\x00\x00",
+            ),
+            (r#""aü😀\n""#, b"\x68a\xC3\xBC\xF0\x9F\x98\x80\x0A"),
+            (
+                "'hi', h'01 02', b64'AQI', b64'-_8='",
+                b"\x42hi\x42\x01\x02\x42\x01\x02\x42\xFB\xFF",
+            ),
+            (r#"<<1, "a">>"#, b"\x43\x01\x61a"),
+            (
+                r#"(_ h'01', h'02'), ''_, ""_"#,
+                b"\x5F\x41\x01\x41\x02\xFF\x5F\xFF\x7F\xFF",
+            ),
+            (
+                r#"[_ ], [_0 1, 2,], {1: [], "a": {_ }}"#,
+                b"\x9F\xFF\x98\x02\x01\x02\xA2\x01\x80\x61a\xBF\xFF",
+            ),
+            (
+                "1_0(simple(16)), 24(<<undefined>>)",
+                b"\xD8\x01\xF0\xD8\x18\x41\xF7",
+            ),
+            ("false, true, null, simple(255)", b"\xF4\xF5\xF6\xF8\xFF"),
+            ("# comment\n [1 / one /, 2]", b"\x82\x01\x02"),
+            ("", b""),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(from_diag(text).unwrap(), expected, "{text}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("[1, 2", DiagError::UnexpectedEnd),
+            ("1 2", DiagError::UnexpectedCharacter(2)),
+            ("[1 / open", DiagError::UnexpectedCharacter(3)),
+            ("1_0x", DiagError::InvalidIndicator(1)),
+            ("300_0", DiagError::InvalidIndicator(0)),
+            ("0.1_1", DiagError::InvalidIndicator(0)),
+            ("-1(2)", DiagError::InvalidNumber(0)),
+            ("simple(24)", DiagError::InvalidNumber(7)),
+            ("h'0'", DiagError::InvalidString(0)),
+            ("h'+1'", DiagError::InvalidString(0)),
+            (r#""\q""#, DiagError::InvalidString(1)),
+            (r#""\u+041""#, DiagError::InvalidString(1)),
+            (r#"(_ h'01', "a")"#, DiagError::InvalidChunk(10)),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(from_diag(text), Err(expected), "{text}");
+        }
+    }
+
+    #[cfg(feature = "de")]
+    #[test]
+    fn roundtrip() {
+        use crate::diag::to_diag;
+
+        let cases: [&[u8]; 5] = [
+            b"\xA2\x01\x42\x01\x02\x61a\x83\x01\xF9\x41\x00\xC0\x74\
+              2013-03-21T20:04:00Z",
+            b"\x9F\x01\x82\x02\x03\x9F\x04\x05\xFF\xFF\xBF\x61a\x01\xFF",
+            b"\x5F\x42\x01\x02\x43\x03\x04\x05\xFF\x7F\xFF\x62\x22\x0A",
+            b"\x18\x17\x98\x01\x01\xD8\x01\x00\x3B\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+            b"\xFB\x3F\xF8\0\0\0\0\0\0\xFA\x47\xC3\x50\x00\xF9\x7C\x00\xFA\x7F\xC0\x00\x00\xF0",
+        ];
+        for bytes in cases {
+            assert_eq!(from_diag(&to_diag(bytes).unwrap()).unwrap(), bytes);
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! Annotated hex dumps of encoded bytes
+//!
+//! Each head gets a line of its own, indented by its nesting level and commented with what
+//! it stands for, the content of strings follows on the next lines:
+//! ```text
+//! A2            # map(2)
+//!    01         # unsigned(1)
+//!    42         # bytes(2)
+//!       0102
+//!    61         # text(1)
+//!       61      # "a"
+//!    9F         # array(*)
+//!       F9 4100 # float(2.5)
+//!       FF      # break
+//! ```
+//! Malformed input is dumped up to the point where it stops being well-formed, the rest of
+//! the bytes follow on a line commented with the error found there
+
+use crate::{de::DecodeOptions, error::DecodeError, float::f16_to_f64, head::Head};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Bytes of string content or malformed input shown on each line
+const BYTES_PER_LINE: usize = 16;
+
+/// Dump the data items in `bytes` (a single one or a CBOR sequence) as annotated hex,
+/// read [crate::dump]
+/// # Considerations
+/// - Nesting is limited to the default [DecodeOptions::max_depth], read
+///   [to_dump_with_options] to change it
+pub fn to_dump(bytes: &[u8]) -> String {
+    to_dump_with_options(bytes, DecodeOptions::default())
+}
+
+/// Same as [to_dump] but with the given [DecodeOptions], only [DecodeOptions::max_depth]
+/// applies
+pub fn to_dump_with_options(bytes: &[u8], options: DecodeOptions) -> String {
+    let mut dump = Dump {
+        bytes,
+        position: 0,
+        depth: 0,
+        max_depth: options.max_depth,
+        indent: 0,
+        lines: Vec::new(),
+    };
+    while dump.position < bytes.len() {
+        if let Err(error) = dump.item(0) {
+            let offset = dump.position;
+            let rest = &bytes[offset..];
+            let comment = format!("error at offset {offset}: {error}");
+            if rest.is_empty() {
+                dump.line(dump.indent, rest, comment);
+            } else {
+                dump.content(dump.indent, rest, comment);
+            }
+            break;
+        }
+    }
+    dump.render()
+}
+
+struct Line {
+    indent: usize,
+    hex: String,
+    comment: String,
+}
+
+struct Dump<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    depth: usize,
+    max_depth: usize,
+    /// Nesting level of what is being read, where an error would be shown
+    indent: usize,
+    lines: Vec<Line>,
+}
+
+impl<'a> Dump<'a> {
+    fn line(&mut self, indent: usize, bytes: &[u8], comment: String) {
+        self.lines.push(Line {
+            indent,
+            hex: hex(bytes),
+            comment,
+        });
+    }
+
+    /// Lines of raw bytes, the comment goes on the first one
+    fn content(&mut self, indent: usize, bytes: &[u8], comment: String) {
+        let mut comment = Some(comment);
+        for chunk in bytes.chunks(BYTES_PER_LINE) {
+            self.line(indent, chunk, comment.take().unwrap_or_default());
+        }
+    }
+
+    /// Read the head at the current position, which is only consumed once it is known
+    /// to be well-formed there
+    fn head(&mut self, indent: usize, allow_break: bool) -> Result<Head, DecodeError> {
+        self.indent = indent;
+        let head = Head::read(&self.bytes[self.position..])?;
+        // 0xFF = break byte
+        if head.initial_byte == 0xFF && !allow_break {
+            return Err(DecodeError::UnexpectedBreak);
+        }
+        Ok(head)
+    }
+
+    /// Write the line of a head and consume it
+    fn head_line(&mut self, indent: usize, head: &Head, comment: String) {
+        let bytes = &self.bytes[self.position..self.position + head.size];
+        let mut hex = hex(&bytes[..1]);
+        if bytes.len() > 1 {
+            hex.push(' ');
+            hex.push_str(&self::hex(&bytes[1..]));
+        }
+        self.lines.push(Line {
+            indent,
+            hex,
+            comment,
+        });
+        self.position += head.size;
+    }
+
+    fn take(&mut self, length: u64) -> Result<&'a [u8], DecodeError> {
+        let bytes = usize::try_from(length)
+            .ok()
+            .and_then(|length| self.bytes[self.position..].get(..length))
+            .ok_or(DecodeError::UnexpectedEof)?;
+        self.position += bytes.len();
+        Ok(bytes)
+    }
+
+    fn recurse(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<(), DecodeError>,
+    ) -> Result<(), DecodeError> {
+        if self.depth >= self.max_depth {
+            return Err(DecodeError::DepthLimitExceeded);
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn item(&mut self, indent: usize) -> Result<(), DecodeError> {
+        let head = self.head(indent, false)?;
+        let length = head
+            .argument
+            .map_or(String::from("*"), |argument| argument.to_string());
+        match (head.major_type(), head.argument) {
+            (0, Some(value)) => self.head_line(indent, &head, format!("unsigned({value})")),
+            (1, Some(value)) => {
+                self.head_line(indent, &head, format!("negative({})", -1 - value as i128))
+            }
+            (2 | 3, Some(length)) => self.string(indent, &head, length)?,
+            (2 | 3, None) => {
+                let kind = kind(&head);
+                self.head_line(indent, &head, format!("{kind}(*)"));
+                self.chunks(indent + 1, head.major_type())?;
+            }
+            (4, _) => {
+                self.head_line(indent, &head, format!("array({length})"));
+                self.recurse(|dump| dump.items(indent + 1, head.argument, 1))?;
+            }
+            (5, _) => {
+                self.head_line(indent, &head, format!("map({length})"));
+                self.recurse(|dump| dump.items(indent + 1, head.argument, 2))?;
+            }
+            (6, Some(tag)) => {
+                self.head_line(indent, &head, format!("tag({tag})"));
+                self.recurse(|dump| dump.item(indent + 1))?;
+            }
+            _ => {
+                let comment = simple_or_float(&head);
+                self.head_line(indent, &head, comment);
+            }
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, indent: usize, head: &Head, length: u64) -> Result<(), DecodeError> {
+        self.head_line(indent, head, format!("{}({length})", kind(head)));
+        self.indent = indent + 1;
+        let content = self.take(length)?;
+        let comment = match head.major_type() {
+            3 => match core::str::from_utf8(content) {
+                Ok(text) => format!("{text:?}"),
+                Err(_) => String::from("invalid UTF-8"),
+            },
+            _ => String::new(),
+        };
+        self.content(indent + 1, content, comment);
+        Ok(())
+    }
+
+    fn chunks(&mut self, indent: usize, major_type: u8) -> Result<(), DecodeError> {
+        loop {
+            let head = self.head(indent, true)?;
+            match (head.major_type(), head.argument) {
+                (7, None) => {
+                    self.head_line(indent, &head, String::from("break"));
+                    return Ok(());
+                }
+                (chunk_type, Some(length)) if chunk_type == major_type => {
+                    self.string(indent, &head, length)?
+                }
+                _ => return Err(DecodeError::InvalidChunk),
+            }
+        }
+    }
+
+    fn items(
+        &mut self,
+        indent: usize,
+        length: Option<u64>,
+        items_per_entry: u64,
+    ) -> Result<(), DecodeError> {
+        match length {
+            Some(length) => {
+                for _ in 0..length.saturating_mul(items_per_entry) {
+                    self.item(indent)?;
+                }
+            }
+            None => loop {
+                let head = self.head(indent, true)?;
+                if head.initial_byte == 0xFF {
+                    self.head_line(indent, &head, String::from("break"));
+                    break;
+                }
+                for _ in 0..items_per_entry {
+                    self.item(indent)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Join the lines, aligning their comments
+    fn render(self) -> String {
+        let width = self
+            .lines
+            .iter()
+            .map(|line| line.indent * 3 + line.hex.len())
+            .max()
+            .unwrap_or(0);
+        let mut output = String::new();
+        for line in self.lines {
+            let code = format!("{}{}", "   ".repeat(line.indent), line.hex);
+            match line.comment.is_empty() {
+                true => output.push_str(&code),
+                false => output.push_str(&format!("{code:<width$} # {}", line.comment)),
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect()
+}
+
+fn kind(head: &Head) -> &'static str {
+    match head.major_type() {
+        2 => "bytes",
+        _ => "text",
+    }
+}
+
+fn simple_or_float(head: &Head) -> String {
+    let argument = head.argument.unwrap_or(0);
+    match head.additional_information() {
+        // 0xF9 = half precision float
+        25 => format!("float({:?})", f16_to_f64(argument as u16)),
+        // 0xFA = single precision float
+        26 => format!("float({:?})", f32::from_bits(argument as u32)),
+        // 0xFB = double precision float
+        27 => format!("float({:?})", f64::from_bits(argument)),
+        _ => match argument {
+            20 => String::from("false"),
+            21 => String::from("true"),
+            22 => String::from("null"),
+            23 => String::from("undefined"),
+            _ => format!("simple({argument})"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump() {
+        let bytes = b"\xA2\x01\x42\x01\x02\x61a\x9F\xF9\x41\x00\xFF";
+        assert_eq!(
+            to_dump(bytes),
+            "\
+A2            # map(2)
+   01         # unsigned(1)
+   42         # bytes(2)
+      0102
+   61         # text(1)
+      61      # \"a\"
+   9F         # array(*)
+      F9 4100 # float(2.5)
+      FF      # break
+"
+        );
+        let bytes = b"\x7F\x61a\x61b\xFF\xC1\x1A\x51\x4B\x67\xB0\x38\xFF";
+        assert_eq!(
+            to_dump(bytes),
+            "\
+7F             # text(*)
+   61          # text(1)
+      61       # \"a\"
+   61          # text(1)
+      62       # \"b\"
+   FF          # break
+C1             # tag(1)
+   1A 514B67B0 # unsigned(1363896240)
+38 FF          # negative(-256)
+"
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        // The array is missing its second item and the map stops in the middle of a head
+        let bytes = b"\x82\x01\xFF\xA1\x19\x01";
+        assert_eq!(
+            to_dump(bytes),
+            "\
+82          # array(2)
+   01       # unsigned(1)
+   FFA11901 # error at offset 2: Unexpected break byte
+"
+        );
+        let bytes = b"\x5F\x41\x01\x61a\xFF";
+        assert_eq!(
+            to_dump(bytes),
+            "\
+5F        # bytes(*)
+   41     # bytes(1)
+      01
+   6161FF # error at offset 3: Invalid chunk inside an indefinite length string
+"
+        );
+        assert_eq!(
+            to_dump(b"\x43\x01"),
+            "43    # bytes(3)\n   01 # error at offset 1: Unexpected end of input\n"
+        );
+    }
+
+    #[test]
+    fn depth_limit() {
+        let options = DecodeOptions {
+            max_depth: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            to_dump_with_options(b"\x81\x00", options),
+            to_dump(b"\x81\x00")
+        );
+        assert_eq!(
+            to_dump_with_options(b"\x81\x81\x00", options),
+            "\
+81    # array(1)
+   81 # array(1)
+   00 # error at offset 2: Nesting depth limit exceeded
+"
+        );
+    }
+}
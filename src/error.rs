@@ -3,50 +3,204 @@
 //! For simplicity and clarity the specific error cases both for encoding and decoding are directly  
 //! declared in its respective enums ([EncodeError] and [DecodeError]) so the [EncodeError::Serialization]  
 //! and [DecodeError::Deserialization] variants, while needed by [serde::ser::Error] and [serde::de::Error]  
-//! trait contracts, are left unused
+//! trait contracts, are only used for errors raised by serde itself (e.g. a missing struct field)
+//!
+//! Without the `std` feature there are no Input/Output errors to wrap, so the `IO` variants do not exist
 
-use serde::{de, ser};
-use std::{fmt::Display, io};
+#[cfg(feature = "alloc")]
+use alloc::string::{String, ToString};
+#[cfg(any(feature = "ser", feature = "de"))]
+use core::fmt::Display;
+#[cfg(feature = "de")]
+use serde::de;
+#[cfg(feature = "ser")]
+use serde::ser;
+#[cfg(feature = "std")]
+use std::io;
 use thiserror::Error;
 
+/// Message of an error raised by serde itself
+#[cfg(feature = "alloc")]
+pub type Message = String;
+
+/// Message of an error raised by serde itself, without the `alloc` feature there is
+/// nowhere to format it into so only a fixed description is kept
+#[cfg(not(feature = "alloc"))]
+pub type Message = &'static str;
+
 /// Represents an error while encoding a CBOR data sequence
 #[cfg(feature = "ser")]
 #[derive(Error, Debug)]
 pub enum EncodeError {
-    /// Unused variant, needed because of [serde::ser::Error] trait contract (read module docs)
-    #[error("Error when serializing")]
-    Serialization(String),
+    /// Error raised by serde itself, needed because of [serde::ser::Error] trait contract (read module docs)
+    #[error("Error when serializing: {0}")]
+    Serialization(Message),
     /// Input/Output error while encoding, usually an error when writing to the [Encoder](crate::ser)'s output
+    #[cfg(feature = "std")]
     #[error("Input/Output error")]
     IO(#[from] io::Error),
     /// The CBOR RFC which this codec is based on does not allow data items with lengths above
     /// 2^64 bytes, this number is absurdly big so it should not be reached
     #[error("Cannot encode lengths above 2^64 bytes")]
     LengthOutOfBounds,
+    /// The output buffer of a [SliceSink](crate::sink::SliceSink) is full, `required` holds
+    /// the size the whole output needs when it is known (e.g. when using [to_slice](crate::to_slice))
+    #[error("Output buffer is too small")]
+    BufferTooSmall { required: Option<usize> },
+    /// Simple values 24 to 31 are reserved by RFC 8949 section 3.3 and have no encoding
+    #[error("Simple value {0} is reserved")]
+    ReservedSimpleValue(u8),
 }
 
 /// Represents an error while decoding a CBOR data sequence
 #[cfg(feature = "de")]
 #[derive(Error, Debug)]
 pub enum DecodeError {
-    /// Unused variant, needed because of [serde::de::Error] trait contract (read module docs)
-    #[error("Error when deserializing")]
-    Deserialization(String),
+    /// Error raised by serde itself, needed because of [serde::de::Error] trait contract (read module docs)
+    #[error("Error when deserializing: {0}")]
+    Deserialization(Message),
     /// Input/Output error while decoding, usually an error when reading from [Decoder](crate::de)'s input
+    #[cfg(feature = "std")]
     #[error("Input/Output error")]
     IO(#[from] io::Error),
+    /// The input ended in the middle of a data item
+    #[error("Unexpected end of input")]
+    UnexpectedEof,
+    /// The header of a data item uses a reserved or meaningless additional information value
+    #[error("Malformed data item header 0x{0:02X}")]
+    MalformedHeader(u8),
+    /// A break byte was found outside of an indefinite length data item
+    #[error("Unexpected break byte")]
+    UnexpectedBreak,
+    /// A chunk of an indefinite length string is not a definite length string of the same major type
+    #[error("Invalid chunk inside an indefinite length string")]
+    InvalidChunk,
+    /// Indefinite length strings have to be concatenated into a buffer, which needs the `alloc` feature
+    #[cfg(not(feature = "alloc"))]
+    #[error("Indefinite length strings are not supported without the alloc feature")]
+    UnsupportedIndefiniteString,
+    /// A text string does not contain valid UTF-8
+    #[error("Text string is not valid UTF-8")]
+    InvalidUtf8,
+    /// The data item found is not the one expected by the type being deserialized
+    #[error("Expected {expected}, found {found}")]
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The simple value cannot be represented in the serde data model
+    #[error("Unsupported simple value {0}")]
+    UnsupportedSimpleValue(u8),
+    /// A simple value below 32 is encoded in two bytes, RFC 8949 section 3.3 does not allow it
+    #[error("Simple value {0} must be encoded in the initial byte")]
+    ReservedSimpleValue(u8),
+    /// An array or map has more or fewer items than the type being deserialized
+    #[error("Container length does not match the expected length")]
+    LengthMismatch,
+    /// A bignum does not fit in a 128 bit integer
+    #[error("Integer does not fit in 128 bits")]
+    IntegerOverflow,
+    /// A length does not fit in memory on this platform
+    #[error("Length out of bounds for this platform")]
+    LengthOutOfBounds,
+    /// The data is nested deeper than [DecodeOptions::max_depth](crate::de::DecodeOptions::max_depth)
+    #[error("Nesting depth limit exceeded")]
+    DepthLimitExceeded,
+    /// There is data left after the decoded data item
+    #[error("Trailing data after the decoded data item")]
+    TrailingData,
+    /// The content of a tag is not of the type the tag requires (e.g. tag 0 on something
+    /// other than a text string), only checked with [DecodeOptions::check_validity](crate::de::DecodeOptions::check_validity)
+    #[error("Invalid content for tag {0}")]
+    InvalidTagContent(u64),
+    /// A map holds the same key more than once, only checked with
+    /// [DecodeOptions::check_validity](crate::de::DecodeOptions::check_validity) or
+    /// [DuplicateKeys::Reject](crate::de::DuplicateKeys::Reject)
+    #[error("Duplicate map key")]
+    DuplicateKey,
+    /// The [Source](crate::source::Source) cannot keep the bytes of a data item to hand
+    /// out as a raw value, read [crate::raw]
+    #[error("Raw values cannot be captured from this source")]
+    RawValueUnsupported,
+}
+
+/// Represents the first problem found when [validating](crate::validate()) encoded data,
+/// `offset` is where the head of the data item (or chunk) at fault starts, or where the
+/// input ends when it is truncated
+#[cfg(feature = "de")]
+#[derive(Error, Debug)]
+#[error("{error} at offset {offset}")]
+pub struct ValidateError {
+    pub offset: usize,
+    pub error: DecodeError,
 }
 
 #[cfg(feature = "ser")]
 impl ser::Error for EncodeError {
+    #[cfg(feature = "alloc")]
     fn custom<T: Display>(msg: T) -> Self {
         Self::Serialization(msg.to_string())
     }
+
+    #[cfg(not(feature = "alloc"))]
+    fn custom<T: Display>(_msg: T) -> Self {
+        Self::Serialization("custom error, enable the alloc feature for details")
+    }
 }
 
 #[cfg(feature = "de")]
 impl de::Error for DecodeError {
+    #[cfg(feature = "alloc")]
     fn custom<T: Display>(msg: T) -> Self {
         Self::Deserialization(msg.to_string())
     }
+
+    #[cfg(not(feature = "alloc"))]
+    fn custom<T: Display>(_msg: T) -> Self {
+        Self::Deserialization("custom error, enable the alloc feature for details")
+    }
+}
+
+/// Represents an error while parsing or following a [Path](crate::value::Path)
+#[cfg(feature = "alloc")]
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PathError {
+    /// A path which is not empty has to start with a slash
+    #[error("Path does not start with a slash")]
+    MissingSlash,
+    /// A segment of the path is malformed (e.g. a byte string with an odd number of hex digits)
+    #[error("Invalid path segment at position {0}")]
+    InvalidSegment(usize),
+    /// The path does not lead to a place where an item can be set
+    #[error("Path not found")]
+    NotFound,
+}
+
+/// Represents an error while parsing [diagnostic notation](crate::diag), variants hold
+/// the byte offset in the text where the problem was found
+#[cfg(feature = "alloc")]
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DiagError {
+    /// The text ended in the middle of a data item
+    #[error("Unexpected end of input")]
+    UnexpectedEnd,
+    /// A character which cannot start or continue the data item being parsed
+    #[error("Unexpected character at offset {0}")]
+    UnexpectedCharacter(usize),
+    /// A number is malformed or out of range (e.g. a tag above 2^64 - 1 or simple value 24)
+    #[error("Invalid number at offset {0}")]
+    InvalidNumber(usize),
+    /// A string is malformed (e.g. an unknown escape, an odd number of hex digits)
+    #[error("Invalid string at offset {0}")]
+    InvalidString(usize),
+    /// An encoding indicator (`_0` to `_3`) is too small for the argument it applies to,
+    /// or does not apply to the data item at all
+    #[error("Invalid encoding indicator at offset {0}")]
+    InvalidIndicator(usize),
+    /// The chunks of an indefinite length string are not all byte strings or all text strings
+    #[error("Invalid chunk inside an indefinite length string at offset {0}")]
+    InvalidChunk(usize),
+    /// The text is nested deeper than the parser allows
+    #[error("Nesting depth limit exceeded at offset {0}")]
+    DepthLimitExceeded(usize),
 }
@@ -0,0 +1,87 @@
+//! Conversions between the float precisions CBOR can encode
+
+/// Convert the bits of an IEEE 754 half precision float into a double
+#[cfg(feature = "de")]
+pub(crate) fn f16_to_f64(bits: u16) -> f64 {
+    let sign = (bits as u64 & 0x8000) << 48;
+    let exponent = (bits >> 10) & 0x1F;
+    let mantissa = (bits & 0x3FF) as u64;
+    match exponent {
+        // Subnormal numbers, mantissa * 2^-24
+        0 => {
+            let magnitude = mantissa as f64 * 5.960464477539063e-8;
+            f64::from_bits(sign | magnitude.to_bits())
+        }
+        // Infinities and NaNs, keeping the NaN payload
+        31 => f64::from_bits(sign | (0x7FF << 52) | (mantissa << 42)),
+        // Normal numbers only need their exponent rebiased from 15 to 1023
+        _ => f64::from_bits(sign | ((exponent as u64 + 1008) << 52) | (mantissa << 42)),
+    }
+}
+
+/// Additional information of the shortest float (0xF9, 0xFA or 0xFB) holding `value`
+/// exactly, which is the preferred serialization of RFC 8949 section 4.1
+#[cfg(any(feature = "ser", feature = "alloc"))]
+pub(crate) fn float_width(value: f64) -> u8 {
+    if f64_to_f16(value).is_some() {
+        25
+    } else if value as f32 as f64 == value {
+        26
+    } else {
+        27
+    }
+}
+
+/// Bits of the half precision float equal to `value`, [None] if there is none
+/// # Considerations
+/// - Every NaN becomes the same quiet NaN (0x7E00), NaN payloads are not kept
+#[cfg(any(feature = "ser", feature = "alloc"))]
+pub(crate) fn f64_to_f16(value: f64) -> Option<u16> {
+    if value.is_nan() {
+        return Some(0x7E00);
+    }
+    let single = value as f32;
+    if single as f64 != value {
+        return None;
+    }
+    let bits = single.to_bits();
+    let sign = (bits >> 16) as u16 & 0x8000;
+    let exponent = ((bits >> 23) & 0xFF) as i32 - 127;
+    let mantissa = bits & 0x7F_FFFF;
+    match exponent {
+        // Zeros
+        -127 if mantissa == 0 => Some(sign),
+        // Infinities
+        128 => Some(sign | 0x7C00),
+        // Normal numbers, dropping the 13 lowest bits of the mantissa
+        -14..=15 if mantissa & 0x1FFF == 0 => {
+            Some(sign | ((exponent + 15) as u16) << 10 | (mantissa >> 13) as u16)
+        }
+        // Subnormal numbers, mantissa * 2^-24 with the implicit leading bit made explicit
+        -24..=-15 => {
+            let shift = (-exponent - 1) as u32;
+            let mantissa = mantissa | 0x80_0000;
+            (mantissa & ((1 << shift) - 1) == 0).then_some(sign | (mantissa >> shift) as u16)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(all(test, feature = "de", any(feature = "ser", feature = "alloc")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_precision() {
+        assert_eq!(f16_to_f64(0x3E00), 1.5);
+        assert_eq!(f16_to_f64(0x0001), 5.960464477539063e-8);
+        assert_eq!(f64_to_f16(1.5), Some(0x3E00));
+        assert_eq!(f64_to_f16(-0.0), Some(0x8000));
+        assert_eq!(f64_to_f16(65504.0), Some(0x7BFF));
+        assert_eq!(f64_to_f16(5.960464477539063e-8), Some(0x0001));
+        assert_eq!(f64_to_f16(65536.0), None);
+        assert_eq!(f64_to_f16(0.1), None);
+        assert_eq!(float_width(100000.0), 26);
+        assert_eq!(float_width(1.1), 27);
+    }
+}
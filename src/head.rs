@@ -0,0 +1,115 @@
+//! Heads of data items read straight from a byte slice
+//!
+//! The [Decoder](crate::de::Decoder) only keeps what a head means, the tools walking
+//! encoded data without decoding it also need to know how it was written
+
+use crate::error::DecodeError;
+
+/// The head of a data item, its initial byte plus its argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Head {
+    pub initial_byte: u8,
+    /// [None] for indefinite length items and the break byte
+    pub argument: Option<u64>,
+    /// Number of bytes the head takes, from 1 to 9
+    pub size: usize,
+}
+
+impl Head {
+    /// Read the head at the start of `bytes`
+    pub fn read(bytes: &[u8]) -> Result<Head, DecodeError> {
+        let initial_byte = *bytes.first().ok_or(DecodeError::UnexpectedEof)?;
+        let size = Head::size(initial_byte)?;
+        let following = bytes.get(1..size).ok_or(DecodeError::UnexpectedEof)?;
+        let argument = match initial_byte & 0x1F {
+            additional_information @ 0..24 => Some(additional_information as u64),
+            31 => None,
+            _ => Some(
+                following
+                    .iter()
+                    .fold(0, |argument, byte| argument << 8 | *byte as u64),
+            ),
+        };
+        let head = Head {
+            initial_byte,
+            argument,
+            size,
+        };
+        match (head.major_type(), argument) {
+            // Integers and tags cannot be indefinite
+            (0 | 1 | 6, None) => Err(DecodeError::MalformedHeader(initial_byte)),
+            // 0xF8 = simple value in the next byte, values below 32 do not need it
+            (7, Some(value)) if initial_byte == 0xF8 && value < 32 => {
+                Err(DecodeError::ReservedSimpleValue(value as u8))
+            }
+            _ => Ok(head),
+        }
+    }
+
+    /// Number of bytes the head starting with `initial_byte` takes
+    pub fn size(initial_byte: u8) -> Result<usize, DecodeError> {
+        match initial_byte & 0x1F {
+            0..24 | 31 => Ok(1),
+            additional_information @ 24..28 => Ok(1 + (1 << (additional_information - 24))),
+            // 28, 29 and 30 are reserved
+            _ => Err(DecodeError::MalformedHeader(initial_byte)),
+        }
+    }
+
+    pub fn major_type(&self) -> u8 {
+        self.initial_byte >> 5
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn additional_information(&self) -> u8 {
+        self.initial_byte & 0x1F
+    }
+
+    #[cfg(feature = "alloc")]
+    /// Whether the argument takes as few bytes as it can (the preferred serialization of
+    /// RFC 8949 section 4.1), which floats and simple values do not apply to
+    pub fn is_shortest(&self) -> bool {
+        match (self.additional_information(), self.argument) {
+            (24, Some(argument)) => argument >= 24,
+            (25, Some(argument)) => argument > 0xFF,
+            (26, Some(argument)) => argument > 0xFFFF,
+            (27, Some(argument)) => argument > 0xFFFF_FFFF,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read() {
+        assert_eq!(
+            Head::read(&[0x19, 0x01, 0xF4, 0x00]).unwrap(),
+            Head {
+                initial_byte: 0x19,
+                argument: Some(500),
+                size: 3
+            }
+        );
+        assert!(!Head::read(&[0x18, 0x17]).unwrap().is_shortest());
+        assert_eq!(Head::read(&[0x9F]).unwrap().argument, None);
+        assert!(matches!(
+            Head::read(&[0x1A, 0x00]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            Head::read(&[0x1C]),
+            Err(DecodeError::MalformedHeader(0x1C))
+        ));
+        assert!(matches!(
+            Head::read(&[0xDF]),
+            Err(DecodeError::MalformedHeader(0xDF))
+        ));
+        assert!(matches!(
+            Head::read(&[0xF8, 0x10]),
+            Err(DecodeError::ReservedSimpleValue(16))
+        ));
+    }
+}
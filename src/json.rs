@@ -0,0 +1,199 @@
+//! Conversions between [Value] and JSON following RFC 8949 section 6
+//!
+//! [to_json] converts a CBOR data item into JSON (section 6.1):
+//! - Integers become numbers, those which fit neither in a `u64` nor in an `i64` become
+//!   strings like bignums do
+//! - Bignums (tags 2 and 3) become their byte string in base64url without padding, the
+//!   negative ones prefixed with `~` (e.g. `-18446744073709551617` is `"~AQAAAAAAAAAA"`)
+//! - Byte strings become base64url without padding, unless they are inside the content of
+//!   an expected conversion tag: base64url for tag 21, base64 with padding for tag 22 and
+//!   lowercase base16 for tag 23
+//! - Text strings, arrays, false, true and null become their JSON counterparts
+//! - Floats become numbers, NaN and the infinities become null
+//! - Other tags become their content, undefined and other simple values become null
+//! - Map keys which do not become a JSON string become the JSON text they convert into
+//!   (e.g. the key `1` becomes `"1"` and `[1, 2]` becomes `"[1,2]"`)
+//!
+//! [from_json] converts JSON into a CBOR data item (section 6.2):
+//! - Numbers without fraction nor exponent which fit in 64 bits become integers, the rest
+//!   become floats
+//! - Strings become text strings, no attempt is made to recover byte strings
+//! - Arrays, objects, false, true and null become their CBOR counterparts, object members
+//!   become map entries with text string keys
+//!
+//! So converting CBOR into JSON and back loses byte strings, tags, simple values and the
+//! types of map keys, while converting JSON into CBOR and back gives the same JSON
+
+use crate::{
+    codec::{self, Base64},
+    value::Value,
+};
+use alloc::string::{String, ToString};
+use serde_json::{Map, Number, Value as Json};
+
+/// How byte strings are written as JSON strings, chosen by the expected conversion tags
+#[derive(Clone, Copy)]
+enum Conversion {
+    Base64Url,
+    Base64,
+    Base16,
+}
+
+/// Convert a CBOR data item into JSON, read [crate::json]
+pub fn to_json(value: &Value) -> Json {
+    convert(value, Conversion::Base64Url)
+}
+
+/// Convert JSON into a CBOR data item, read [crate::json]
+pub fn from_json(json: &Json) -> Value {
+    match json {
+        Json::Null => Value::Null,
+        Json::Bool(value) => Value::Bool(*value),
+        Json::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(value), _) => Value::Integer(value as i128),
+            (_, Some(value)) => Value::Integer(value as i128),
+            _ => Value::Float(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        Json::String(text) => Value::Text(text.clone()),
+        Json::Array(items) => Value::Array(items.iter().map(from_json).collect()),
+        Json::Object(members) => Value::Map(
+            members
+                .iter()
+                .map(|(key, value)| (Value::Text(key.clone()), from_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn convert(value: &Value, conversion: Conversion) -> Json {
+    match value {
+        Value::Integer(value) => match (u64::try_from(*value), i64::try_from(*value)) {
+            (Ok(value), _) => Json::from(value),
+            (_, Ok(value)) => Json::from(value),
+            // JSON numbers hold 64 bit integers only, so it is written like a bignum
+            _ => {
+                let magnitude = match *value < 0 {
+                    true => (-1 - *value) as u128,
+                    false => *value as u128,
+                };
+                let bytes = magnitude.to_be_bytes();
+                let start = bytes.iter().position(|&byte| byte != 0).unwrap_or(15);
+                bignum(*value < 0, &bytes[start..])
+            }
+        },
+        Value::Bytes(bytes) => Json::String(encode(bytes, conversion)),
+        Value::Text(text) => Json::String(text.clone()),
+        Value::Array(items) => {
+            Json::Array(items.iter().map(|item| convert(item, conversion)).collect())
+        }
+        Value::Map(entries) => {
+            let mut members = Map::new();
+            for (key, value) in entries {
+                let key = match convert(key, conversion) {
+                    Json::String(key) => key,
+                    key => key.to_string(),
+                };
+                members.insert(key, convert(value, conversion));
+            }
+            Json::Object(members)
+        }
+        Value::Tag(tag @ (2 | 3), content) if matches!(**content, Value::Bytes(_)) => {
+            let Value::Bytes(bytes) = &**content else {
+                unreachable!("the content is a byte string")
+            };
+            bignum(*tag == 3, bytes)
+        }
+        Value::Tag(21, content) => convert(content, Conversion::Base64Url),
+        Value::Tag(22, content) => convert(content, Conversion::Base64),
+        Value::Tag(23, content) => convert(content, Conversion::Base16),
+        Value::Tag(_, content) => convert(content, conversion),
+        Value::Float(value) => Number::from_f64(*value).map_or(Json::Null, Json::Number),
+        Value::Bool(value) => Json::Bool(*value),
+        Value::Simple(_) | Value::Null | Value::Undefined => Json::Null,
+    }
+}
+
+/// A bignum is its byte string in base64url, prefixed with `~` when it is negative
+fn bignum(negative: bool, bytes: &[u8]) -> Json {
+    let mut text = String::new();
+    if negative {
+        text.push('~');
+    }
+    text.push_str(&encode(bytes, Conversion::Base64Url));
+    Json::String(text)
+}
+
+fn encode(bytes: &[u8], conversion: Conversion) -> String {
+    match conversion {
+        Conversion::Base64Url => codec::encode_base64(bytes, Base64::Url),
+        Conversion::Base64 => codec::encode_base64(bytes, Base64::Standard),
+        Conversion::Base16 => codec::encode_hex(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor;
+    use serde_json::json;
+
+    #[test]
+    fn cbor_to_json() {
+        let value = cbor!({
+            "bytes" => b"\xFB\xFF\x00",
+            1 => [-1, 1.5, f64::NAN, true, null, undefined, simple(32)],
+            [1, "a"] => tag(1, 1700000000),
+            "tagged" => tag(22, [b"foob", tag(23, b"\x01\xAB")]),
+            "url" => tag(22, tag(21, b"\xFB")),
+        });
+        assert_eq!(
+            to_json(&value),
+            json!({
+                "bytes": "-_8A",
+                "1": [-1, 1.5, null, true, null, null, null],
+                "[1,\"a\"]": 1700000000,
+                "tagged": ["Zm9vYg==", "01ab"],
+                "url": "-w",
+            })
+        );
+    }
+
+    #[test]
+    fn bignums() {
+        let value = cbor!([
+            u64::MAX,
+            i64::MIN,
+            u64::MAX as i128 + 1,
+            -2 - u64::MAX as i128,
+            tag(
+                2,
+                b"\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"
+            ),
+            tag(3, "not a bignum"),
+        ]);
+        assert_eq!(
+            to_json(&value),
+            json!([
+                u64::MAX,
+                i64::MIN,
+                "AQAAAAAAAAAA",
+                "~AQAAAAAAAAAA",
+                "AQAAAAAAAAAAAAAAAAAAAAA",
+                "not a bignum",
+            ])
+        );
+    }
+
+    #[test]
+    fn json_to_cbor() {
+        let json = json!({"a": [1, -1, 1.0, 1e300, u64::MAX, "x"], "b": {"c": null, "d": false}});
+        assert_eq!(
+            from_json(&json),
+            cbor!({
+                "a" => [1, -1, 1.0, 1e300, u64::MAX, "x"],
+                "b" => { "c" => null, "d" => false },
+            })
+        );
+        assert_eq!(to_json(&from_json(&json)), json);
+    }
+}
@@ -1,10 +1,408 @@
 //! A CBOR implementation for the serde framework
+//!
+//! # Features
+//! - `std` (default): support for [std::io] readers and writers, implies `alloc`
+//! - `alloc` (default through `std`): support for heap allocated outputs, map sorting,
+//!   definite length buffering, [Value](value::Value) and [diagnostic notation](diag),
+//!   without it the crate works on `no_std` targets encoding into and decoding from byte slices
+//! - `ser` (default): the encoder
+//! - `de` (default): the decoder
+//! - `json`: conversions between [Value](value::Value) and JSON following RFC 8949 section 6
+//! - `cli`: the `cbored` command-line tool, which prints, checks and converts CBOR data
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
 #[cfg(not(any(feature = "ser", feature = "de")))]
 compile_error!("You must enable either \"ser\" or \"de\" features");
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+mod codec;
+#[cfg(feature = "de")]
+pub mod cursor;
 #[cfg(feature = "de")]
 pub mod de;
+#[cfg(feature = "alloc")]
+pub mod diag;
+#[cfg(all(feature = "de", feature = "alloc"))]
+pub mod dump;
 pub mod error;
+mod float;
+#[cfg(feature = "de")]
+mod head;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "alloc")]
+mod macros;
+pub mod raw;
 #[cfg(feature = "ser")]
 pub mod ser;
+pub mod simple;
+#[cfg(feature = "ser")]
+pub mod sink;
+#[cfg(feature = "de")]
+pub mod source;
+pub mod tag;
+pub mod transcode;
+#[cfg(feature = "de")]
+mod validate;
+#[cfg(feature = "alloc")]
+pub mod value;
+
+/// Items used by the exported macros and the `cbored` tool, not part of the public API
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::codec::{Base64, decode_base64, decode_hex, encode_base64, encode_hex};
+    pub use alloc::{boxed::Box, vec};
+}
+
+#[cfg(all(feature = "ser", feature = "std"))]
+use crate::sink::IoSink;
+#[cfg(all(feature = "de", feature = "std"))]
+use crate::source::IoSource;
+#[cfg(feature = "de")]
+use crate::{
+    de::{DecodeOptions, Decoder},
+    error::{DecodeError, ValidateError},
+    source::SliceSource,
+};
+#[cfg(feature = "ser")]
+use crate::{
+    error::EncodeError,
+    ser::{EncodeOptions, Encoder},
+    sink::SliceSink,
+};
+#[cfg(all(feature = "ser", feature = "alloc"))]
+use alloc::vec::Vec;
+#[cfg(feature = "de")]
+use serde::de::Deserialize;
+#[cfg(all(feature = "de", feature = "alloc"))]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "ser")]
+use serde::ser::Serialize;
+#[cfg(feature = "std")]
+use std::io;
+
+/// Encode `value` into `writer`, flushing it before returning so no error goes unnoticed
+/// # Considerations
+/// - The writer is not buffered by the [Encoder], read [IoSink]
+#[cfg(all(feature = "ser", feature = "std"))]
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<(), EncodeError>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    to_writer_with_options(writer, value, EncodeOptions::default())
+}
+
+/// Same as [to_writer] but with the given [EncodeOptions]
+#[cfg(all(feature = "ser", feature = "std"))]
+pub fn to_writer_with_options<W, T>(
+    writer: W,
+    value: &T,
+    options: EncodeOptions,
+) -> Result<(), EncodeError>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    let mut encoder = Encoder::with_options(IoSink::new(writer), options);
+    value.serialize(&mut encoder)?;
+    encoder.into_inner().map(|_| ())
+}
+
+/// Encode `value` into a newly allocated vector
+#[cfg(all(feature = "ser", feature = "alloc"))]
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>, EncodeError>
+where
+    T: ?Sized + Serialize,
+{
+    to_vec_with_options(value, EncodeOptions::default())
+}
+
+/// Same as [to_vec] but with the given [EncodeOptions]
+#[cfg(all(feature = "ser", feature = "alloc"))]
+pub fn to_vec_with_options<T>(value: &T, options: EncodeOptions) -> Result<Vec<u8>, EncodeError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut encoder = Encoder::with_options(Vec::new(), options);
+    value.serialize(&mut encoder)?;
+    encoder.into_inner()
+}
+
+/// Encode `value` into `buffer` without allocating, returning the number of bytes used
+/// # Considerations
+/// - If `buffer` is too small the value is encoded a second time, without writing it,
+///   to report the required size in [EncodeError::BufferTooSmall]
+/// - The contents of `buffer` past the returned length are unspecified
+#[cfg(feature = "ser")]
+pub fn to_slice<T>(value: &T, buffer: &mut [u8]) -> Result<usize, EncodeError>
+where
+    T: ?Sized + Serialize,
+{
+    to_slice_with_options(value, buffer, EncodeOptions::default())
+}
+
+/// Same as [to_slice] but with the given [EncodeOptions]
+#[cfg(feature = "ser")]
+pub fn to_slice_with_options<T>(
+    value: &T,
+    buffer: &mut [u8],
+    options: EncodeOptions,
+) -> Result<usize, EncodeError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut encoder = Encoder::with_options(SliceSink::new(buffer), options);
+    match value.serialize(&mut encoder) {
+        Ok(()) => Ok(encoder.into_inner()?.position()),
+        Err(EncodeError::BufferTooSmall { .. }) => Err(EncodeError::BufferTooSmall {
+            required: Some(serialized_size_with_options(value, options)?),
+        }),
+        Err(error) => Err(error),
+    }
+}
+
+/// Compute the exact number of bytes [Encoder] would produce for `value`,
+/// without writing nor allocating anything
+#[cfg(feature = "ser")]
+pub fn serialized_size<T>(value: &T) -> Result<usize, EncodeError>
+where
+    T: ?Sized + Serialize,
+{
+    serialized_size_with_options(value, EncodeOptions::default())
+}
+
+/// Same as [serialized_size] but with the given [EncodeOptions]
+#[cfg(feature = "ser")]
+pub fn serialized_size_with_options<T>(
+    value: &T,
+    options: EncodeOptions,
+) -> Result<usize, EncodeError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut encoder = Encoder::counter(options);
+    value.serialize(&mut encoder)?;
+    Ok(encoder.into_inner()?.count())
+}
+
+/// Convert `value` into a [Value](value::Value) without going through bytes
+/// # Considerations
+/// - The result is laid out the way the [Encoder] writes `value`, so decoding it
+///   or its encoding gives the same result, but integers are written in their
+///   shortest form instead of the width of their Rust type
+#[cfg(all(feature = "ser", feature = "alloc"))]
+pub fn to_value<T>(value: &T) -> Result<value::Value, EncodeError>
+where
+    T: ?Sized + Serialize,
+{
+    value.serialize(value::ValueSerializer)
+}
+
+/// Decode a single data item from `reader`, failing with [DecodeError::TrailingData]
+/// if there is anything left after it
+/// # Considerations
+/// - The reader is wrapped in a [std::io::BufReader], use [Decoder] with an
+///   [IoSource] to decode from a reader which is already buffered
+#[cfg(all(feature = "de", feature = "std"))]
+pub fn from_reader<R, T>(reader: R) -> Result<T, DecodeError>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    from_reader_with_options(reader, DecodeOptions::default())
+}
+
+/// Same as [from_reader] but with the given [DecodeOptions]
+#[cfg(all(feature = "de", feature = "std"))]
+pub fn from_reader_with_options<R, T>(reader: R, options: DecodeOptions) -> Result<T, DecodeError>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    let mut decoder = Decoder::with_options(IoSource::new(io::BufReader::new(reader)), options);
+    let value = T::deserialize(&mut decoder)?;
+    decoder.end()?;
+    Ok(value)
+}
+
+/// Decode a single data item from `slice`, failing with [DecodeError::TrailingData]
+/// if there is anything left after it, byte and text strings may be borrowed from `slice`
+#[cfg(feature = "de")]
+pub fn from_slice<'de, T>(slice: &'de [u8]) -> Result<T, DecodeError>
+where
+    T: Deserialize<'de>,
+{
+    from_slice_with_options(slice, DecodeOptions::default())
+}
+
+/// Same as [from_slice] but with the given [DecodeOptions]
+#[cfg(feature = "de")]
+pub fn from_slice_with_options<'de, T>(
+    slice: &'de [u8],
+    options: DecodeOptions,
+) -> Result<T, DecodeError>
+where
+    T: Deserialize<'de>,
+{
+    let mut decoder = Decoder::with_options(SliceSource::new(slice), options);
+    let value = T::deserialize(&mut decoder)?;
+    decoder.end()?;
+    Ok(value)
+}
+
+/// Check `bytes` holds well-formed data items (a single one or a CBOR sequence, which
+/// may be empty) as RFC 8949 Appendix C defines them, without decoding them
+/// # Considerations
+/// - Only the heads are looked at: reserved additional information values (28 to 30),
+///   indefinite length integers and tags, breaks outside of indefinite length items,
+///   chunks of indefinite length strings which are not definite length strings of the
+///   same major type, two byte simple values below 32 and truncated data items are found
+/// - Nothing is allocated, nesting is limited by [DecodeOptions::max_depth]
+/// - [DecodeOptions::check_validity] also checks the data items are valid, looking for
+///   duplicate map keys allocates though
+/// - Duplicate map keys are an error only when [DecodeOptions::duplicate_keys] rejects
+///   them, keeping the first or the last one is left to decoding
+#[cfg(feature = "de")]
+pub fn validate(bytes: &[u8]) -> Result<(), ValidateError> {
+    validate_with_options(bytes, DecodeOptions::default())
+}
+
+/// Same as [validate()] but with the given [DecodeOptions]
+#[cfg(feature = "de")]
+pub fn validate_with_options(bytes: &[u8], options: DecodeOptions) -> Result<(), ValidateError> {
+    validate::validate(bytes, options)
+}
+
+/// Same as [validate()] but reading the data items from `reader`
+/// # Considerations
+/// - The reader is not wrapped in a [std::io::BufReader] so nothing is allocated, wrap
+///   it yourself if it performs a system call on every read
+#[cfg(all(feature = "de", feature = "std"))]
+pub fn validate_reader<R: io::Read>(reader: R) -> Result<(), ValidateError> {
+    validate_reader_with_options(reader, DecodeOptions::default())
+}
+
+/// Same as [validate_reader] but with the given [DecodeOptions]
+#[cfg(all(feature = "de", feature = "std"))]
+pub fn validate_reader_with_options<R: io::Read>(
+    reader: R,
+    options: DecodeOptions,
+) -> Result<(), ValidateError> {
+    validate::validate(validate::ReaderInput::new(reader), options)
+}
+
+/// Convert a [Value](value::Value) into `T` without going through bytes, the
+/// counterpart of [to_value]
+#[cfg(all(feature = "de", feature = "alloc"))]
+pub fn from_value<T>(value: value::Value) -> Result<T, DecodeError>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(value)
+}
+
+#[cfg(all(test, feature = "ser", feature = "de", feature = "std"))]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Rectangle(u32, u32),
+        Polygon { sides: u8, name: String },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Drawing {
+        id: u64,
+        offset: i32,
+        title: Option<String>,
+        shapes: Vec<Shape>,
+        tags: BTreeMap<String, bool>,
+        #[serde(with = "serde_bytes")]
+        thumbnail: Vec<u8>,
+    }
+
+    fn drawing() -> Drawing {
+        Drawing {
+            id: 7,
+            offset: -300,
+            title: Some(String::from("sketch")),
+            shapes: Vec::from([
+                Shape::Point,
+                Shape::Circle(1.5),
+                Shape::Rectangle(2, 3),
+                Shape::Polygon {
+                    sides: 6,
+                    name: String::from("hexagon"),
+                },
+            ]),
+            tags: BTreeMap::from([(String::from("b"), true), (String::from("a"), false)]),
+            thumbnail: Vec::from([0xDE, 0xAD]),
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let encoded = to_vec(&drawing()).unwrap();
+        assert_eq!(from_slice::<Drawing>(&encoded).unwrap(), drawing());
+        assert_eq!(
+            from_reader::<_, Drawing>(encoded.as_slice()).unwrap(),
+            drawing()
+        );
+    }
+
+    #[test]
+    fn borrowed_strings() {
+        #[derive(Serialize, Deserialize)]
+        struct Message<'a> {
+            kind: &'a str,
+            #[serde(with = "serde_bytes")]
+            payload: &'a [u8],
+        }
+        let encoded = to_vec(&Message {
+            kind: "ping",
+            payload: &[0x01, 0x02],
+        })
+        .unwrap();
+        let message: Message = from_slice(&encoded).unwrap();
+        assert_eq!((message.kind, message.payload), ("ping", &[0x01, 0x02][..]));
+        // Readers cannot lend their data
+        let mut decoder = Decoder::new(IoSource::new(encoded.as_slice()));
+        assert!(Message::deserialize(&mut decoder).is_err());
+    }
+
+    #[test]
+    fn trailing_data() {
+        let mut encoded = to_vec(&1u8).unwrap();
+        encoded.push(0x00);
+        assert!(matches!(
+            from_slice::<u8>(&encoded),
+            Err(DecodeError::TrailingData)
+        ));
+    }
+
+    #[test]
+    fn big_integers() {
+        // 0xC2 = tag(2), unsigned bignum of 9 bytes
+        let encoded = to_vec(&(u64::MAX as u128 + 1)).unwrap();
+        assert_eq!(encoded, [0xC2, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(from_slice::<u128>(&encoded).unwrap(), u64::MAX as u128 + 1);
+        for value in [i128::MIN, -1, 0, i128::MAX] {
+            assert_eq!(from_slice::<i128>(&to_vec(&value).unwrap()).unwrap(), value);
+        }
+        assert!(matches!(
+            from_slice::<i128>(&to_vec(&u128::MAX).unwrap()),
+            Err(DecodeError::Deserialization(_))
+        ));
+    }
+}
@@ -0,0 +1,139 @@
+//! Macros building [Value](crate::value::Value) trees
+
+/// Build a [Value](crate::value::Value) from a literal-like syntax
+///
+/// - `null`, `undefined`, `true` and `false` are the simple values of the same name
+/// - `simple(n)` is the simple value `n` and `tag(n, item)` encloses `item` in the tag `n`
+/// - `[a, b, ...]` is an array and `{ key => value, ... }` a map, whose keys can be any item
+/// - Anything else is an expression converted with [From] (e.g. `-1`, `"text"`, `b"bytes"`)
+///
+/// ```
+/// use serde_cbored::{cbor, value::Value};
+///
+/// let value = cbor!({
+///     1 => "one",
+///     b"id" => [tag(1, 1700000000), null],
+///     "nested" => { -1 => simple(32) },
+/// });
+/// assert!(matches!(value, Value::Map(entries) if entries.len() == 3));
+/// ```
+#[macro_export]
+macro_rules! cbor {
+    (null) => {
+        $crate::value::Value::Null
+    };
+    (undefined) => {
+        $crate::value::Value::Undefined
+    };
+    (true) => {
+        $crate::value::Value::Bool(true)
+    };
+    (false) => {
+        $crate::value::Value::Bool(false)
+    };
+    (simple($value:expr)) => {
+        $crate::value::Value::from($crate::simple::Simple($value))
+    };
+    (tag($tag:expr, $($value:tt)+)) => {
+        $crate::value::Value::Tag($tag, $crate::__private::Box::new($crate::cbor!($($value)+)))
+    };
+    ([$($items:tt)*]) => {
+        $crate::value::Value::Array($crate::cbor!(@array [] [] $($items)*))
+    };
+    ({$($entries:tt)*}) => {
+        $crate::value::Value::Map($crate::cbor!(@map [] [] $($entries)*))
+    };
+    ($other:expr) => {
+        $crate::value::Value::from($other)
+    };
+
+    // Array items are the tokens between commas, gathered in the second bracket
+    (@array [$($done:expr,)*] []) => {
+        $crate::__private::vec![$($done,)*]
+    };
+    (@array [$($done:expr,)*] [$($item:tt)+]) => {
+        $crate::__private::vec![$($done,)* $crate::cbor!($($item)+),]
+    };
+    (@array [$($done:expr,)*] [$($item:tt)+] , $($rest:tt)*) => {
+        $crate::cbor!(@array [$($done,)* $crate::cbor!($($item)+),] [] $($rest)*)
+    };
+    (@array [$($done:expr,)*] [$($item:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::cbor!(@array [$($done,)*] [$($item)* $next] $($rest)*)
+    };
+
+    // Map keys are the tokens before `=>`, gathered in the second bracket
+    (@map [$($done:expr,)*] []) => {
+        $crate::__private::vec![$($done,)*]
+    };
+    (@map [$($done:expr,)*] [$($key:tt)+] => $($rest:tt)+) => {
+        $crate::cbor!(@entry [$($done,)*] [$($key)+] [] $($rest)+)
+    };
+    (@map [$($done:expr,)*] [$($key:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::cbor!(@map [$($done,)*] [$($key)* $next] $($rest)*)
+    };
+
+    // Map values are the tokens between `=>` and the next comma, gathered in the third bracket
+    (@entry [$($done:expr,)*] [$($key:tt)+] [$($value:tt)+]) => {
+        $crate::cbor!(@map [$($done,)* ($crate::cbor!($($key)+), $crate::cbor!($($value)+)),] [])
+    };
+    (@entry [$($done:expr,)*] [$($key:tt)+] [$($value:tt)+] , $($rest:tt)*) => {
+        $crate::cbor!(@map [$($done,)* ($crate::cbor!($($key)+), $crate::cbor!($($value)+)),] [] $($rest)*)
+    };
+    (@entry [$($done:expr,)*] [$($key:tt)+] [$($value:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::cbor!(@entry [$($done,)*] [$($key)+] [$($value)* $next] $($rest)*)
+    };
+}
+
+#[cfg(all(test, feature = "ser", feature = "std"))]
+mod tests {
+    use crate::{to_vec, value::Value};
+
+    #[test]
+    fn cbor() {
+        let value = cbor!({
+            1 => "one",
+            -2 => [1.5, null, undefined, true, simple(32), simple(20)],
+            b"\x00" => tag(1, {}),
+            [1, 2] => tag(2, tag(3, b"")),
+            "nested" => { "empty" => [] },
+        });
+        let expected = Value::Map(Vec::from([
+            (Value::Integer(1), Value::Text(String::from("one"))),
+            (
+                Value::Integer(-2),
+                Value::Array(Vec::from([
+                    Value::Float(1.5),
+                    Value::Null,
+                    Value::Undefined,
+                    Value::Bool(true),
+                    Value::Simple(32),
+                    Value::Bool(false),
+                ])),
+            ),
+            (
+                Value::Bytes(Vec::from([0x00])),
+                Value::Tag(1, Box::new(Value::Map(Vec::new()))),
+            ),
+            (
+                Value::Array(Vec::from([Value::Integer(1), Value::Integer(2)])),
+                Value::Tag(
+                    2,
+                    Box::new(Value::Tag(3, Box::new(Value::Bytes(Vec::new())))),
+                ),
+            ),
+            (
+                Value::Text(String::from("nested")),
+                Value::Map(Vec::from([(
+                    Value::Text(String::from("empty")),
+                    Value::Array(Vec::new()),
+                )])),
+            ),
+        ]));
+        assert_eq!(value, expected);
+        let id = 7u8;
+        assert_eq!(
+            to_vec(&cbor!([id, Some("x"), None::<u8>])).unwrap(),
+            Vec::from([0x83, 0x07, 0x61, 0x78, 0xF6])
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! Data items kept as they are encoded
+//!
+//! [RawValueRef] and [RawValue] capture the exact bytes of one data item instead of
+//! decoding it, and write those bytes back verbatim, so a sub-structure (e.g. a signed
+//! payload) stays byte-identical while the document around it is decoded and re-encoded
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//! use serde_cbored::raw::RawValueRef;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Envelope<'a> {
+//!     #[serde(borrow)]
+//!     payload: RawValueRef<'a>,
+//!     signature: u8,
+//! }
+//!
+//! // {"payload": [1, 1], "signature": 7} with the second 1 not in preferred serialization
+//! let input = b"\xA2\x67payload\x82\x01\x18\x01\x69signature\x07";
+//! let envelope: Envelope = serde_cbored::from_slice(input).unwrap();
+//! assert_eq!(envelope.payload.as_bytes(), b"\x82\x01\x18\x01");
+//! assert_eq!(serde_cbored::to_vec(&envelope).unwrap(), input);
+//! ```
+//!
+//! Like tags and simple values they go through a newtype struct with a reserved name,
+//! which the [Encoder](crate::ser::Encoder) and [Decoder](crate::de::Decoder) recognize
+//!
+//! # Considerations
+//! - The data item is checked to be well-formed while it is captured, and valid if
+//!   [DecodeOptions::check_validity](crate::de::DecodeOptions::check_validity) is set,
+//!   duplicate keys inside it are only rejected, never dropped
+//! - Other serde formats see the newtype struct as a byte string holding the encoded data
+//!   item, except [to_value](crate::to_value) which decodes it
+//! - Raw values cannot be deserialized from a [Value](crate::value::Value)
+
+#[cfg(feature = "de")]
+use crate::{error::DecodeError, from_slice};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::{fmt, marker::PhantomData};
+use serde::{
+    de::{self, Deserialize, Deserializer, Visitor},
+    ser::{Serialize, Serializer},
+};
+
+/// Name of the newtype struct used to pass raw data items between the codec and
+/// [RawValueRef] or [RawValue]
+pub(crate) const RAW_NAME: &str = "@@RAW@@";
+
+/// A single encoded data item borrowed from the input, read [crate::raw]
+/// # Considerations
+/// - It can only be deserialized from a [SliceSource](crate::source::SliceSource), use
+///   [RawValue] with sources which copy their input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawValueRef<'a>(&'a [u8]);
+
+impl<'a> RawValueRef<'a> {
+    /// Construct a raw value from `bytes`, which have to hold exactly one well-formed data item
+    #[cfg(feature = "de")]
+    pub fn new(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+        from_slice(bytes)
+    }
+
+    /// The encoded data item
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Decode the data item into `T`, strings may be borrowed from it
+    #[cfg(feature = "de")]
+    pub fn deserialize_into<T: Deserialize<'a>>(&self) -> Result<T, DecodeError> {
+        from_slice(self.0)
+    }
+
+    /// Copy the data item into a [RawValue]
+    #[cfg(feature = "alloc")]
+    pub fn into_owned(self) -> RawValue {
+        RawValue(self.0.to_vec())
+    }
+}
+
+impl Serialize for RawValueRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(RAW_NAME, &Bytes(self.0))
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawValueRef<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(RAW_NAME, RawValueRefVisitor(PhantomData))
+    }
+}
+
+struct RawValueRefVisitor<'a>(PhantomData<&'a ()>);
+
+impl<'de: 'a, 'a> Visitor<'de> for RawValueRefVisitor<'a> {
+    type Value = RawValueRef<'a>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a data item borrowed from the input")
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+        Ok(RawValueRef(v))
+    }
+}
+
+/// A single encoded data item, read [crate::raw]
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawValue(Vec<u8>);
+
+#[cfg(feature = "alloc")]
+impl RawValue {
+    /// Construct a raw value from `bytes`, which have to hold exactly one well-formed data item
+    #[cfg(feature = "de")]
+    pub fn new(bytes: Vec<u8>) -> Result<Self, DecodeError> {
+        RawValueRef::new(&bytes)?;
+        Ok(Self(bytes))
+    }
+
+    /// The encoded data item
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Return the encoded data item
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Decode the data item into `T`, strings may be borrowed from it
+    #[cfg(feature = "de")]
+    pub fn deserialize_into<'a, T: Deserialize<'a>>(&'a self) -> Result<T, DecodeError> {
+        from_slice(&self.0)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<&'a RawValue> for RawValueRef<'a> {
+    fn from(value: &'a RawValue) -> Self {
+        RawValueRef(&value.0)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Serialize for RawValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RawValueRef::from(self).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de> Deserialize<'de> for RawValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(RAW_NAME, RawValueVisitor)
+    }
+}
+
+#[cfg(feature = "alloc")]
+struct RawValueVisitor;
+
+#[cfg(feature = "alloc")]
+impl<'de> Visitor<'de> for RawValueVisitor {
+    type Value = RawValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a data item")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(RawValue(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(RawValue(v))
+    }
+}
+
+/// Hands the encoded data item to the serializer as a byte string
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+#[cfg(all(test, feature = "ser", feature = "de", feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{
+        de::{DecodeOptions, Decoder, DuplicateKeys},
+        from_reader, from_slice_with_options,
+        source::{IoSource, SliceSource},
+        to_value, to_vec,
+        value::Value,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Envelope<T> {
+        payload: T,
+        signature: u8,
+    }
+
+    #[test]
+    fn capture() {
+        // {"payload": {"b": 1, "a": [_ 1.5]}, "signature": 7}, unsorted keys, an f64
+        // holding 1.5 and an indefinite length array all kept as they are
+        let payload = b"\xA2\x61b\x01\x61a\x9F\xFB\x3F\xF8\x00\x00\x00\x00\x00\x00\xFF";
+        let mut input = b"\xA2\x67payload".to_vec();
+        input.extend(payload);
+        input.extend(b"\x69signature\x07");
+
+        let borrowed: Envelope<RawValueRef> = from_slice(&input).unwrap();
+        assert_eq!(borrowed.payload.as_bytes(), payload);
+        assert_eq!(to_vec(&borrowed).unwrap(), input);
+        let owned: Envelope<RawValue> = from_reader(&input[..]).unwrap();
+        assert_eq!(owned.payload.as_bytes(), payload);
+        assert_eq!(to_vec(&owned).unwrap(), input);
+        let mut decoder = Decoder::new(IoSource::new(&input[..]));
+        assert!(Envelope::<RawValueRef>::deserialize(&mut decoder).is_err());
+
+        let decoded: BTreeMap<String, Value> = owned.payload.deserialize_into().unwrap();
+        assert_eq!(decoded["b"], Value::Integer(1));
+        assert_eq!(
+            to_value(&owned).unwrap(),
+            to_value(&Envelope {
+                payload: decoded,
+                signature: 7
+            })
+            .unwrap()
+        );
+
+        // The source is left right after the captured data item
+        let mut decoder = Decoder::new(SliceSource::new(b"\x81\x00\x01"));
+        let raw = RawValueRef::deserialize(&mut decoder).unwrap();
+        assert_eq!(raw.as_bytes(), b"\x81\x00");
+        assert_eq!(u8::deserialize(&mut decoder).unwrap(), 1);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(RawValueRef::new(b"\x82\x01").is_err());
+        assert!(RawValueRef::new(b"\x01\x02").is_err());
+        assert!(RawValue::new(Vec::new()).is_err());
+        assert_eq!(
+            RawValue::new(b"\xF6".to_vec()).unwrap().into_bytes(),
+            b"\xF6"
+        );
+        // Validity is checked while capturing when asked for, {"a": 1, "a": 2}
+        let options = DecodeOptions {
+            check_validity: true,
+            ..Default::default()
+        };
+        let duplicate = b"\x81\xA2\x61a\x01\x61a\x02";
+        assert!(from_slice::<RawValueRef>(duplicate).is_ok());
+        assert!(matches!(
+            from_slice_with_options::<RawValueRef>(duplicate, options),
+            Err(DecodeError::DuplicateKey)
+        ));
+        assert!(matches!(
+            from_slice_with_options::<RawValueRef>(b"\x61\xFF", options),
+            Err(DecodeError::InvalidUtf8)
+        ));
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        // {"payload": [1, 1], "signature": 7}, then with "signature": 8 added
+        let unique = b"\xA2\x67payload\x82\x01\x18\x01\x69signature\x07";
+        let duplicate = b"\xA3\x67payload\x82\x01\x18\x01\x69signature\x07\x69signature\x08";
+        for (duplicate_keys, signature) in [
+            (DuplicateKeys::Unchecked, None),
+            (DuplicateKeys::Reject, None),
+            (DuplicateKeys::KeepFirst, Some(7)),
+            (DuplicateKeys::KeepLast, Some(8)),
+        ] {
+            let options = DecodeOptions {
+                check_validity: true,
+                duplicate_keys,
+                ..Default::default()
+            };
+            let envelope: Envelope<RawValueRef> = from_slice_with_options(unique, options).unwrap();
+            assert_eq!(envelope.payload.as_bytes(), b"\x82\x01\x18\x01");
+            let envelope = from_slice_with_options::<Envelope<RawValueRef>>(duplicate, options);
+            let Some(signature) = signature else {
+                assert!(matches!(envelope, Err(DecodeError::DuplicateKey)));
+                continue;
+            };
+            let envelope = envelope.unwrap();
+            assert_eq!(envelope.payload.as_bytes(), b"\x82\x01\x18\x01");
+            assert_eq!(envelope.signature, signature);
+            let mut decoder = Decoder::with_options(IoSource::new(&duplicate[..]), options);
+            let owned = Envelope::<RawValue>::deserialize(&mut decoder).unwrap();
+            assert_eq!(owned.payload.as_bytes(), b"\x82\x01\x18\x01");
+        }
+    }
+}
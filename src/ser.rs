@@ -1,23 +1,92 @@
 //! The CBOR encoder
 
-use crate::error::EncodeError;
+use crate::{
+    error::EncodeError,
+    float::{f64_to_f16, float_width},
+    raw::RAW_NAME,
+    simple::SIMPLE_NAME,
+    sink::{CountingSink, Sink},
+    tag::TAG_NAME,
+};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 use serde::ser::{
     Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
     SerializeTupleStruct, SerializeTupleVariant, Serializer,
 };
-use std::io::{BufWriter, Write};
+
+/// Options changing the output of the [Encoder]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// Write map entries (including struct fields) sorted by the bytewise lexicographic
+    /// order of their encoded keys
+    /// # Considerations
+    /// Every entry of a map is buffered in memory until the map ends
+    #[cfg(feature = "alloc")]
+    pub sort_maps: bool,
+    /// Write arrays and maps of unknown length as definite length data items instead
+    /// of indefinite length ones
+    /// # Considerations
+    /// Every item of such array or map is buffered in memory until it ends
+    #[cfg(feature = "alloc")]
+    pub definite_lengths: bool,
+    /// Write floats in the shortest of half, single and double precision which holds their
+    /// value exactly, the preferred serialization of RFC 8949 section 4.1
+    /// # Considerations
+    /// Every NaN is written as the same half precision quiet NaN (0xF97E00)
+    pub shortest_floats: bool,
+}
 
 /// The encoder type
 /// # Considerations
-/// - This type is buffered, read [Encoder::flush]
-pub struct Encoder<W: Write> {
-    writer: BufWriter<W>,
+/// - The encoder writes straight into its [Sink], which may be buffered, read [Encoder::flush]
+pub struct Encoder<W: Sink> {
+    writer: W,
+    options: EncodeOptions,
+    bytes_written: u64,
+    /// The next unsigned integer is not a data item on its own but part of a
+    /// CBOR specific type, read [crate::tag] and [crate::simple]
+    pending: Option<PendingHeader>,
+    /// The output is only being measured, so collections never need to be buffered
+    #[cfg(feature = "alloc")]
+    counting: bool,
 }
 
 /// The complex encoder type
-pub struct ComplexEncoder<'encoder, W: Write> {
+pub struct ComplexEncoder<'encoder, W: Sink> {
     encoder: &'encoder mut Encoder<W>,
-    indefinite_length: bool,
+    mode: CollectionMode,
+}
+
+/// How the items of an array or map reach the output
+enum CollectionMode {
+    /// Items are written straight to the output, followed by a break byte if indefinite length
+    Direct { indefinite_length: bool },
+    /// Items are encoded into a buffer so the header can hold the final length,
+    /// read [EncodeOptions::definite_lengths]
+    #[cfg(feature = "alloc")]
+    Buffered {
+        major_type: u8,
+        buffer: Vec<u8>,
+        length: usize,
+    },
+    /// Map entries are encoded apart and written sorted by their encoded keys,
+    /// read [EncodeOptions::sort_maps]
+    #[cfg(feature = "alloc")]
+    Sorted { entries: Vec<(Vec<u8>, Vec<u8>)> },
+    /// Items are only counted so the header can be accounted for at the end,
+    /// replaces the other modes when the [Encoder] is measuring its output
+    #[cfg(feature = "alloc")]
+    Counted { major_type: u8, length: usize },
+}
+
+/// Header a CBOR specific type is waiting for its unsigned integer to write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PendingHeader {
+    Tag,
+    Simple,
+    /// The next byte string is a whole encoded data item, written as it is, read [crate::raw]
+    Raw,
 }
 
 enum LengthPlacement {
@@ -28,54 +97,202 @@ enum LengthPlacement {
     NextEightBytes,
 }
 
-impl<W: Write> Encoder<W> {
+impl<W: Sink> Encoder<W> {
     /// Construct a new encoder, which will write its output into `W`
     pub fn new(destination: W) -> Self {
+        Self::with_options(destination, EncodeOptions::default())
+    }
+
+    /// Construct a new encoder with the given [EncodeOptions]
+    pub fn with_options(destination: W, options: EncodeOptions) -> Self {
         Self {
-            writer: BufWriter::new(destination),
+            writer: destination,
+            options,
+            bytes_written: 0,
+            pending: None,
+            #[cfg(feature = "alloc")]
+            counting: false,
         }
     }
 
-    /// The [Encoder] is buffered, this means that while you might have finished
-    /// encoding data, this inner buffer could have CBOR data pending to be written
+    /// Flush the [Encoder] and return its output, unlike dropping it, errors
+    /// while flushing are reported
+    pub fn into_inner(mut self) -> Result<W, EncodeError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Get a reference to the output
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get a mutable reference to the output
+    /// # Considerations
+    /// If the [Sink] is buffered, call [Encoder::flush] before writing directly
+    /// into its destination or the data will be interleaved
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// The options this encoder was constructed with
+    pub fn options(&self) -> EncodeOptions {
+        self.options
+    }
+
+    /// Number of bytes encoded so far, including the ones the [Sink] may still be buffering
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// The [Sink] of the [Encoder] may be buffered (e.g. an [IoSink](crate::sink::IoSink)
+    /// over a [std::io::BufWriter]), this means that while you might have finished
+    /// encoding data, this buffer could have CBOR data pending to be written
     /// to the output, this method tries to flush this buffer, ensuring all pending
     /// data is written to its output
     /// # Considerations
-    /// When the [Encoder] is dropped, flush() will be called automatically by the
-    /// [std::ops::Drop] trait, but any errors that might occur during this process
-    /// will be ignored, therefore, its highly recommendable to call this method
+    /// Buffered writers usually flush themselves when dropped, but any errors that
+    /// might occur during this process will be ignored, therefore, its highly
+    /// recommendable to call this method or [Encoder::into_inner]
     pub fn flush(&mut self) -> Result<(), EncodeError> {
-        Ok(self.writer.flush()?)
+        self.writer.flush()
     }
 
     fn write_u8(&mut self, data: u8) -> Result<(), EncodeError> {
-        Ok(self.writer.write_all(&[data])?)
+        self.write_bytes(&[data])
     }
 
     fn write_u16(&mut self, data: u16) -> Result<(), EncodeError> {
-        Ok(self.writer.write_all(&data.to_be_bytes())?)
+        self.write_bytes(&data.to_be_bytes())
     }
 
     fn write_u32(&mut self, data: u32) -> Result<(), EncodeError> {
-        Ok(self.writer.write_all(&data.to_be_bytes())?)
+        self.write_bytes(&data.to_be_bytes())
     }
 
     fn write_u64(&mut self, data: u64) -> Result<(), EncodeError> {
-        Ok(self.writer.write_all(&data.to_be_bytes())?)
+        self.write_bytes(&data.to_be_bytes())
     }
 
     fn write_bytes(&mut self, data: &[u8]) -> Result<(), EncodeError> {
-        Ok(self.writer.write_all(data)?)
+        self.writer.write_all(data)?;
+        self.bytes_written += data.len() as u64;
+        Ok(())
+    }
+
+    /// Write the header of a data item whose argument is a length
+    fn write_header(&mut self, major_type: u8, length: usize) -> Result<(), EncodeError> {
+        match Self::calc_length_placement(length)? {
+            LengthPlacement::AdditionalInformation => self.write_u8(major_type | length as u8),
+            // 0x18 = argument in the next byte
+            LengthPlacement::NextByte => self.write_bytes(&[major_type | 0x18, length as u8]),
+            LengthPlacement::NextTwoBytes => {
+                // 0x19 = argument in the next two bytes
+                self.write_u8(major_type | 0x19)?;
+                self.write_u16(length as u16)
+            }
+            LengthPlacement::NextFourBytes => {
+                // 0x1A = argument in the next four bytes
+                self.write_u8(major_type | 0x1A)?;
+                self.write_u32(length as u32)
+            }
+            LengthPlacement::NextEightBytes => {
+                // 0x1B = argument in the next eight bytes
+                self.write_u8(major_type | 0x1B)?;
+                self.write_u64(length as u64)
+            }
+        }
+    }
+
+    /// Write the header of a data item with the shortest encoding of `argument`
+    fn write_argument(&mut self, major_type: u8, argument: u64) -> Result<(), EncodeError> {
+        match argument {
+            0..24 => self.write_u8(major_type | argument as u8),
+            // 0x18 = argument in the next byte
+            24..=0xFF => self.write_bytes(&[major_type | 0x18, argument as u8]),
+            0x100..=0xFFFF => {
+                // 0x19 = argument in the next two bytes
+                self.write_u8(major_type | 0x19)?;
+                self.write_u16(argument as u16)
+            }
+            0x1_0000..=0xFFFF_FFFF => {
+                // 0x1A = argument in the next four bytes
+                self.write_u8(major_type | 0x1A)?;
+                self.write_u32(argument as u32)
+            }
+            _ => {
+                // 0x1B = argument in the next eight bytes
+                self.write_u8(major_type | 0x1B)?;
+                self.write_u64(argument)
+            }
+        }
+    }
+
+    /// Write a float in the shortest precision holding it, read [EncodeOptions::shortest_floats]
+    fn write_shortest_float(&mut self, value: f64) -> Result<(), EncodeError> {
+        match float_width(value) {
+            // 0xF9 = half precision float in the next two bytes
+            25 => {
+                self.write_u8(0xF9)?;
+                self.write_u16(f64_to_f16(value).expect("the value fits in half precision"))
+            }
+            // 0xFA = single precision float in the next four bytes
+            26 => {
+                self.write_u8(0xFA)?;
+                self.write_u32((value as f32).to_bits())
+            }
+            // 0xFB = double precision float in the next eight bytes
+            _ => {
+                self.write_u8(0xFB)?;
+                self.write_u64(value.to_bits())
+            }
+        }
+    }
+
+    /// Write a bignum tag followed by the big endian bytes of `magnitude`, without leading zeros
+    fn write_bignum(&mut self, tag: u8, magnitude: u128) -> Result<(), EncodeError> {
+        let bytes = magnitude.to_be_bytes();
+        let start = (magnitude.leading_zeros() / 8) as usize;
+        self.write_u8(tag)?;
+        self.serialize_bytes(&bytes[start..])
+    }
+
+    /// Mode of a collection whose length is unknown but has to be written as definite
+    #[cfg(feature = "alloc")]
+    fn buffered_mode(&self, major_type: u8) -> CollectionMode {
+        if self.counting {
+            CollectionMode::Counted {
+                major_type,
+                length: 0,
+            }
+        } else {
+            CollectionMode::Buffered {
+                major_type,
+                buffer: Vec::new(),
+                length: 0,
+            }
+        }
+    }
+
+    /// Encode `value` on its own with the same options, appending the result to `buffer`
+    #[cfg(feature = "alloc")]
+    fn encode_into<T>(&self, value: &T, buffer: &mut Vec<u8>) -> Result<(), EncodeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut encoder = Encoder::with_options(buffer, self.options);
+        value.serialize(&mut encoder)?;
+        encoder.into_inner().map(|_| ())
     }
 
     fn calc_length_placement(length: usize) -> Result<LengthPlacement, EncodeError> {
         if length < 24 {
             Ok(LengthPlacement::AdditionalInformation)
-        } else if length < u8::MAX as usize {
+        } else if length <= u8::MAX as usize {
             Ok(LengthPlacement::NextByte)
-        } else if length < u16::MAX as usize {
+        } else if length <= u16::MAX as usize {
             Ok(LengthPlacement::NextTwoBytes)
-        } else if length < u32::MAX as usize {
+        } else if length <= u32::MAX as usize {
             Ok(LengthPlacement::NextFourBytes)
         } else if length < u64::MAX as usize {
             Ok(LengthPlacement::NextEightBytes)
@@ -85,7 +302,19 @@ impl<W: Write> Encoder<W> {
     }
 }
 
-impl<'encoder, W: Write> Serializer for &'encoder mut Encoder<W> {
+impl Encoder<CountingSink> {
+    /// Construct an encoder which only measures the size of its output, without
+    /// allocating even when the options would require buffering collections
+    pub(crate) fn counter(options: EncodeOptions) -> Self {
+        Self {
+            #[cfg(feature = "alloc")]
+            counting: true,
+            ..Self::with_options(CountingSink::new(), options)
+        }
+    }
+}
+
+impl<'encoder, W: Sink> Serializer for &'encoder mut Encoder<W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -152,6 +381,17 @@ impl<'encoder, W: Write> Serializer for &'encoder mut Encoder<W> {
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        if self.pending == Some(PendingHeader::Simple) {
+            self.pending = None;
+            return match v {
+                // 0xE0 = simple value major type
+                0..24 => self.write_u8(0xE0 | v),
+                // Simple values 24 to 31 are reserved so 0xF8 never holds one below 32
+                24..32 => Err(EncodeError::ReservedSimpleValue(v)),
+                // 0xF8 = simple value in the next byte
+                _ => self.write_bytes(&[0xF8, v]),
+            };
+        }
         if v < 24 {
             // 0x00 = unsigned integer major type
             // Since ORing with 0x00 has no effect and v is guaranteed to be below 24 we directly write v
@@ -175,17 +415,57 @@ impl<'encoder, W: Write> Serializer for &'encoder mut Encoder<W> {
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        if self.pending == Some(PendingHeader::Tag) {
+            self.pending = None;
+            // 0xC0 = tag major type
+            return self.write_argument(0xC0, v);
+        }
         // 0x1B = unsigned integer in the next eight bytes
         self.write_u8(0x1B)?;
         self.write_u64(v)
     }
 
-    fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Self::Error> {
-        todo!()
+    // 128 bit integers have no fixed width counterpart in CBOR, so the shortest encoding
+    // is used and values out of the 64 bit range become bignums (RFC 8949 section 3.4.3)
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        if v < 0 {
+            let encoded_value = (-1 - v) as u128;
+            match u64::try_from(encoded_value) {
+                // 0x20 = negative integer major type
+                Ok(encoded_value) => self.write_argument(0x20, encoded_value),
+                // 0xC3 = tag(3), negative bignum
+                Err(_) => self.write_bignum(0xC3, encoded_value),
+            }
+        } else {
+            self.serialize_u128(v as u128)
+        }
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        match u64::try_from(v) {
+            // 0x00 = unsigned integer major type
+            Ok(v) => self.write_argument(0x00, v),
+            // 0xC2 = tag(2), unsigned bignum
+            Err(_) => self.write_bignum(0xC2, v),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        if self.options.shortest_floats {
+            return self.write_shortest_float(v as f64);
+        }
+        // 0xFA = single precision float in the next four bytes
+        self.write_u8(0xFA)?;
+        self.write_u32(v.to_bits())
     }
 
-    fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Self::Error> {
-        todo!()
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        if self.options.shortest_floats {
+            return self.write_shortest_float(v);
+        }
+        // 0xFB = double precision float in the next eight bytes
+        self.write_u8(0xFB)?;
+        self.write_u64(v.to_bits())
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
@@ -224,6 +504,10 @@ impl<'encoder, W: Write> Serializer for &'encoder mut Encoder<W> {
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        if self.pending == Some(PendingHeader::Raw) {
+            self.pending = None;
+            return self.write_bytes(v);
+        }
         let v_length = v.len();
         match Encoder::<W>::calc_length_placement(v_length)? {
             // 0x40 = byte string major type
@@ -282,12 +566,17 @@ impl<'encoder, W: Write> Serializer for &'encoder mut Encoder<W> {
 
     fn serialize_newtype_struct<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
         T: ?Sized + Serialize,
     {
+        match name {
+            SIMPLE_NAME => self.pending = Some(PendingHeader::Simple),
+            RAW_NAME => self.pending = Some(PendingHeader::Raw),
+            _ => {}
+        }
         value.serialize(self)
     }
 
@@ -310,12 +599,20 @@ impl<'encoder, W: Write> Serializer for &'encoder mut Encoder<W> {
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
         match len {
             Some(length) => self.serialize_tuple(length),
+            #[cfg(feature = "alloc")]
+            None if self.options.definite_lengths => Ok(ComplexEncoder {
+                // 0x80 = array of data items major type
+                mode: self.buffered_mode(0x80),
+                encoder: self,
+            }),
             None => {
                 // 0x9F = array of data items, indefinite length
                 self.write_u8(0x9F)?;
                 Ok(ComplexEncoder {
                     encoder: self,
-                    indefinite_length: true,
+                    mode: CollectionMode::Direct {
+                        indefinite_length: true,
+                    },
                 })
             }
         }
@@ -345,15 +642,28 @@ impl<'encoder, W: Write> Serializer for &'encoder mut Encoder<W> {
         }
         Ok(ComplexEncoder {
             encoder: self,
-            indefinite_length: false,
+            mode: CollectionMode::Direct {
+                indefinite_length: false,
+            },
         })
     }
 
+    // Tags arrive as a tuple struct holding the tag number and the enclosed value,
+    // the number becomes the tag header and the value follows it
     fn serialize_tuple_struct(
         self,
-        _name: &'static str,
+        name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        if name == TAG_NAME {
+            self.pending = Some(PendingHeader::Tag);
+            return Ok(ComplexEncoder {
+                encoder: self,
+                mode: CollectionMode::Direct {
+                    indefinite_length: false,
+                },
+            });
+        }
         self.serialize_tuple(len)
     }
 
@@ -374,6 +684,21 @@ impl<'encoder, W: Write> Serializer for &'encoder mut Encoder<W> {
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
         match len {
+            // Sorting does not change the size of a map, so it is skipped while measuring
+            #[cfg(feature = "alloc")]
+            _ if self.options.sort_maps && !self.counting => Ok(ComplexEncoder {
+                encoder: self,
+                mode: CollectionMode::Sorted {
+                    entries: Vec::with_capacity(len.unwrap_or(0)),
+                },
+            }),
+            // Sorted maps always have a definite length
+            #[cfg(feature = "alloc")]
+            None if self.options.definite_lengths || self.options.sort_maps => Ok(ComplexEncoder {
+                // 0xA0 = map major type
+                mode: self.buffered_mode(0xA0),
+                encoder: self,
+            }),
             Some(length) => {
                 match Encoder::<W>::calc_length_placement(length)? {
                     LengthPlacement::AdditionalInformation => {
@@ -402,7 +727,9 @@ impl<'encoder, W: Write> Serializer for &'encoder mut Encoder<W> {
                 }
                 Ok(ComplexEncoder {
                     encoder: self,
-                    indefinite_length: false,
+                    mode: CollectionMode::Direct {
+                        indefinite_length: false,
+                    },
                 })
             }
             None => {
@@ -410,7 +737,9 @@ impl<'encoder, W: Write> Serializer for &'encoder mut Encoder<W> {
                 self.write_u8(0xBF)?;
                 Ok(ComplexEncoder {
                     encoder: self,
-                    indefinite_length: true,
+                    mode: CollectionMode::Direct {
+                        indefinite_length: true,
+                    },
                 })
             }
         }
@@ -442,9 +771,150 @@ impl<'encoder, W: Write> Serializer for &'encoder mut Encoder<W> {
         let map_encoder = self.serialize_map(Some(len))?;
         Ok(map_encoder)
     }
+
+    // Without a buffer to format the value into, it is formatted twice,
+    // once to measure its length and once to write it
+    #[cfg(not(feature = "alloc"))]
+    fn collect_str<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + core::fmt::Display,
+    {
+        use core::fmt::Write;
+
+        struct LengthCounter(usize);
+
+        impl Write for LengthCounter {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                self.0 += s.len();
+                Ok(())
+            }
+        }
+
+        struct EncoderWriter<'encoder, W: Sink> {
+            encoder: &'encoder mut Encoder<W>,
+            error: Option<EncodeError>,
+        }
+
+        impl<W: Sink> Write for EncoderWriter<'_, W> {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                self.encoder.write_bytes(s.as_bytes()).map_err(|error| {
+                    self.error = Some(error);
+                    core::fmt::Error
+                })
+            }
+        }
+
+        let mut counter = LengthCounter(0);
+        write!(counter, "{value}").map_err(|_| {
+            <EncodeError as serde::ser::Error>::custom("error formatting the value")
+        })?;
+        // 0x60 = text string major type
+        self.write_header(0x60, counter.0)?;
+        let mut writer = EncoderWriter {
+            encoder: self,
+            error: None,
+        };
+        match write!(writer, "{value}") {
+            Ok(()) => Ok(()),
+            Err(_) => Err(writer.error.take().unwrap_or_else(|| {
+                <EncodeError as serde::ser::Error>::custom("error formatting the value")
+            })),
+        }
+    }
+}
+
+impl<'encoder, W: Sink> ComplexEncoder<'encoder, W> {
+    fn encode_element<T>(&mut self, value: &T) -> Result<(), EncodeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        match &mut self.mode {
+            CollectionMode::Direct { .. } => value.serialize(&mut *self.encoder),
+            #[cfg(feature = "alloc")]
+            CollectionMode::Buffered { buffer, length, .. } => {
+                *length += 1;
+                self.encoder.encode_into(value, buffer)
+            }
+            #[cfg(feature = "alloc")]
+            CollectionMode::Counted { length, .. } => {
+                *length += 1;
+                value.serialize(&mut *self.encoder)
+            }
+            #[cfg(feature = "alloc")]
+            CollectionMode::Sorted { .. } => unreachable!("sorted mode is only used by maps"),
+        }
+    }
+
+    fn encode_key<T>(&mut self, key: &T) -> Result<(), EncodeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        match &mut self.mode {
+            #[cfg(feature = "alloc")]
+            CollectionMode::Sorted { entries } => {
+                let mut encoded_key = Vec::new();
+                self.encoder.encode_into(key, &mut encoded_key)?;
+                entries.push((encoded_key, Vec::new()));
+                Ok(())
+            }
+            _ => self.encode_element(key),
+        }
+    }
+
+    fn encode_value<T>(&mut self, value: &T) -> Result<(), EncodeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        match &mut self.mode {
+            #[cfg(feature = "alloc")]
+            CollectionMode::Buffered { buffer, .. } => self.encoder.encode_into(value, buffer),
+            #[cfg(feature = "alloc")]
+            CollectionMode::Sorted { entries } => match entries.last_mut() {
+                Some((_, encoded_value)) => self.encoder.encode_into(value, encoded_value),
+                None => Err(<EncodeError as serde::ser::Error>::custom(
+                    "map value serialized before its key",
+                )),
+            },
+            _ => value.serialize(&mut *self.encoder),
+        }
+    }
+
+    fn finish(self) -> Result<(), EncodeError> {
+        match self.mode {
+            // 0xFF = break byte
+            CollectionMode::Direct { indefinite_length } if indefinite_length => {
+                self.encoder.write_u8(0xFF)
+            }
+            CollectionMode::Direct { .. } => Ok(()),
+            #[cfg(feature = "alloc")]
+            CollectionMode::Buffered {
+                major_type,
+                buffer,
+                length,
+            } => {
+                self.encoder.write_header(major_type, length)?;
+                self.encoder.write_bytes(&buffer)
+            }
+            #[cfg(feature = "alloc")]
+            CollectionMode::Counted { major_type, length } => {
+                self.encoder.write_header(major_type, length)
+            }
+            #[cfg(feature = "alloc")]
+            CollectionMode::Sorted { mut entries } => {
+                entries.sort_by(|(key_a, _), (key_b, _)| key_a.cmp(key_b));
+                // 0xA0 = map major type
+                self.encoder.write_header(0xA0, entries.len())?;
+                for (key, value) in entries {
+                    self.encoder.write_bytes(&key)?;
+                    self.encoder.write_bytes(&value)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
-impl<'encoder, W: Write> SerializeSeq for ComplexEncoder<'encoder, W> {
+impl<'encoder, W: Sink> SerializeSeq for ComplexEncoder<'encoder, W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -452,20 +922,15 @@ impl<'encoder, W: Write> SerializeSeq for ComplexEncoder<'encoder, W> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut *self.encoder)
+        self.encode_element(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        // 0xFF = break byte
-        if self.indefinite_length {
-            self.encoder.write_u8(0xFF)
-        } else {
-            Ok(())
-        }
+        self.finish()
     }
 }
 
-impl<'encoder, W: Write> SerializeTuple for ComplexEncoder<'encoder, W> {
+impl<'encoder, W: Sink> SerializeTuple for ComplexEncoder<'encoder, W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -473,15 +938,15 @@ impl<'encoder, W: Write> SerializeTuple for ComplexEncoder<'encoder, W> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut *self.encoder)
+        self.encode_element(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        self.finish()
     }
 }
 
-impl<'encoder, W: Write> SerializeTupleStruct for ComplexEncoder<'encoder, W> {
+impl<'encoder, W: Sink> SerializeTupleStruct for ComplexEncoder<'encoder, W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -489,15 +954,15 @@ impl<'encoder, W: Write> SerializeTupleStruct for ComplexEncoder<'encoder, W> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut *self.encoder)
+        self.encode_element(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        self.finish()
     }
 }
 
-impl<'encoder, W: Write> SerializeTupleVariant for ComplexEncoder<'encoder, W> {
+impl<'encoder, W: Sink> SerializeTupleVariant for ComplexEncoder<'encoder, W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -505,15 +970,15 @@ impl<'encoder, W: Write> SerializeTupleVariant for ComplexEncoder<'encoder, W> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut *self.encoder)
+        self.encode_element(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        self.finish()
     }
 }
 
-impl<'encoder, W: Write> SerializeMap for ComplexEncoder<'encoder, W> {
+impl<'encoder, W: Sink> SerializeMap for ComplexEncoder<'encoder, W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -521,27 +986,22 @@ impl<'encoder, W: Write> SerializeMap for ComplexEncoder<'encoder, W> {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut *self.encoder)
+        self.encode_key(key)
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut *self.encoder)
+        self.encode_value(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        // 0xFF = break byte
-        if self.indefinite_length {
-            self.encoder.write_u8(0xFF)
-        } else {
-            Ok(())
-        }
+        self.finish()
     }
 }
 
-impl<'encoder, W: Write> SerializeStruct for ComplexEncoder<'encoder, W> {
+impl<'encoder, W: Sink> SerializeStruct for ComplexEncoder<'encoder, W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -549,17 +1009,16 @@ impl<'encoder, W: Write> SerializeStruct for ComplexEncoder<'encoder, W> {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut *self.encoder)?;
-        value.serialize(&mut *self.encoder)?;
-        Ok(())
+        self.encode_key(key)?;
+        self.encode_value(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        self.finish()
     }
 }
 
-impl<'encoder, W: Write> SerializeStructVariant for ComplexEncoder<'encoder, W> {
+impl<'encoder, W: Sink> SerializeStructVariant for ComplexEncoder<'encoder, W> {
     type Ok = ();
     type Error = EncodeError;
 
@@ -567,13 +1026,12 @@ impl<'encoder, W: Write> SerializeStructVariant for ComplexEncoder<'encoder, W>
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut *self.encoder)?;
-        value.serialize(&mut *self.encoder)?;
-        Ok(())
+        self.encode_key(key)?;
+        self.encode_value(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        self.finish()
     }
 }
 
@@ -680,4 +1138,190 @@ mod tests {
         }
         assert_eq!(buffer, expected_cbor);
     }
+
+    #[test]
+    fn fixed_size_buffer() {
+        let mut buffer = [0; 8];
+        let input_data = (1u8, "abc");
+        let expected_cbor = [
+            0x82,                   // array(2)
+            0x01,                   // 1
+            0x63, 0x61, 0x62, 0x63  // "abc"
+        ];
+        let used = crate::to_slice(&input_data, &mut buffer).unwrap();
+        assert_eq!(buffer[..used], expected_cbor);
+        // The size required is reported when the buffer is too small
+        assert!(matches!(
+            crate::to_slice(&input_data, &mut buffer[..4]),
+            Err(EncodeError::BufferTooSmall { required: Some(6) })
+        ));
+    }
+
+    #[test]
+    fn serialize_f32() {
+        let mut buffer = Vec::new();
+        let input_data: Vec<f32> = Vec::from([0.0, -0.0, 1.5]);
+        let expected_cbor = Vec::from([
+            0xFA, 0x00, 0x00, 0x00, 0x00, // 0.0
+            0xFA, 0x80, 0x00, 0x00, 0x00, // -0.0
+            0xFA, 0x3F, 0xC0, 0x00, 0x00  // 1.5
+        ]);
+        {
+            let mut encoder = Encoder::new(&mut buffer);
+            for single_input in input_data {
+                encoder.serialize_f32(single_input).unwrap();
+            }
+            encoder.flush().unwrap();
+        }
+        assert_eq!(buffer, expected_cbor);
+    }
+
+    #[test]
+    fn serialize_f64() {
+        let mut buffer = Vec::new();
+        let input_data: Vec<f64> = Vec::from([1.5, 1.1]);
+        let expected_cbor = Vec::from([
+            0xFB, 0x3F, 0xF8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 1.5
+            0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A  // 1.1
+        ]);
+        {
+            let mut encoder = Encoder::new(&mut buffer);
+            for single_input in input_data {
+                encoder.serialize_f64(single_input).unwrap();
+            }
+            encoder.flush().unwrap();
+        }
+        assert_eq!(buffer, expected_cbor);
+    }
+
+    #[test]
+    fn sort_maps() {
+        let mut buffer = Vec::new();
+        let options = EncodeOptions {
+            sort_maps: true,
+            ..Default::default()
+        };
+        let input_data = [("bb", 1u8), ("a", 2u8), ("c", 3u8)];
+        // Shorter keys sort first since their header byte is lower
+        let expected_cbor = Vec::from([
+            0xA3,                   // map(3)
+            0x61, 0x61, 0x02,       // "a": 2
+            0x61, 0x63, 0x03,       // "c": 3
+            0x62, 0x62, 0x62, 0x01  // "bb": 1
+        ]);
+        {
+            let mut encoder = Encoder::with_options(&mut buffer, options);
+            encoder.collect_map(input_data).unwrap();
+            encoder.flush().unwrap();
+        }
+        assert_eq!(buffer, expected_cbor);
+    }
+
+    #[test]
+    fn definite_lengths() {
+        let mut buffer = Vec::new();
+        let options = EncodeOptions {
+            definite_lengths: true,
+            ..Default::default()
+        };
+        // Filtered iterators have no known length, so they would be written with an indefinite length
+        let input_data = [1u8, 2u8];
+        let expected_cbor = Vec::from([
+            0x82,       // array(2)
+            0x01, 0x02  // 1, 2
+        ]);
+        {
+            let mut encoder = Encoder::with_options(&mut buffer, options);
+            encoder.collect_seq(input_data.iter().filter(|_| true)).unwrap();
+            encoder.flush().unwrap();
+        }
+        assert_eq!(buffer, expected_cbor);
+    }
+
+    #[test]
+    fn length_placement() {
+        let mut buffer = Vec::new();
+        let input_data = [23, 24, 255, 256, 65535, 65536];
+        let expected_heads: [&[u8]; 6] = [
+            &[0x57],                         // 23
+            &[0x58, 0x18],                   // 24
+            &[0x58, 0xFF],                   // 255
+            &[0x59, 0x01, 0x00],             // 256
+            &[0x59, 0xFF, 0xFF],             // 65535
+            &[0x5A, 0x00, 0x01, 0x00, 0x00]  // 65536
+        ];
+        for (length, expected_head) in input_data.into_iter().zip(expected_heads) {
+            buffer.clear();
+            {
+                let mut encoder = Encoder::new(&mut buffer);
+                encoder.serialize_bytes(&alloc::vec![0; length]).unwrap();
+                encoder.flush().unwrap();
+            }
+            assert_eq!(&buffer[..expected_head.len()], expected_head);
+            assert_eq!(buffer.len(), expected_head.len() + length);
+        }
+    }
+
+    #[test]
+    fn into_inner() {
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.serialize_u8(24).unwrap();
+        assert_eq!(encoder.bytes_written(), 2);
+        assert_eq!(encoder.get_ref(), &[0x18, 0x18]);
+        encoder.serialize_bool(true).unwrap();
+        assert_eq!(encoder.bytes_written(), 3);
+        assert_eq!(encoder.into_inner().unwrap(), Vec::from([0x18, 0x18, 0xF5]));
+    }
+
+    #[test]
+    fn counter() {
+        // A map of unknown length, buffered by the other encoders when sorting maps or
+        // writing definite lengths, which the counter only measures
+        let input_data = [("b", [1u8, 2u8]), ("a", [3u8, 4u8])];
+        for sort_maps in [false, true] {
+            for definite_lengths in [false, true] {
+                let mut buffer = Vec::new();
+                let options = EncodeOptions {
+                    sort_maps,
+                    definite_lengths,
+                    ..Default::default()
+                };
+                {
+                    let mut encoder = Encoder::with_options(&mut buffer, options);
+                    encoder.collect_map(input_data.into_iter().filter(|_| true)).unwrap();
+                    encoder.flush().unwrap();
+                }
+                let mut counter = Encoder::counter(options);
+                counter.collect_map(input_data.into_iter().filter(|_| true)).unwrap();
+                assert_eq!(counter.into_inner().unwrap().count(), buffer.len());
+            }
+        }
+    }
+
+    #[test]
+    fn shortest_floats() {
+        let mut buffer = Vec::new();
+        let options = EncodeOptions {
+            shortest_floats: true,
+            ..Default::default()
+        };
+        let input_data: Vec<f64> = Vec::from([1.5, f64::NAN, -0.0, 100000.0, 1.1]);
+        let expected_cbor = Vec::from([
+            0xF9, 0x3E, 0x00,                                     // 1.5
+            0xF9, 0x7E, 0x00,                                     // NaN
+            0xF9, 0x80, 0x00,                                     // -0.0
+            0xFA, 0x47, 0xC3, 0x50, 0x00,                         // 100000.0
+            0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A, // 1.1
+            0xF9, 0x3E, 0x00                                      // 1.5 as f32
+        ]);
+        {
+            let mut encoder = Encoder::with_options(&mut buffer, options);
+            for single_input in input_data {
+                encoder.serialize_f64(single_input).unwrap();
+            }
+            encoder.serialize_f32(1.5).unwrap();
+            encoder.flush().unwrap();
+        }
+        assert_eq!(buffer, expected_cbor);
+    }
 }
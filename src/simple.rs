@@ -0,0 +1,143 @@
+//! CBOR simple values (major type 7)
+//!
+//! Only false, true and null have a counterpart in the serde data model, the rest of
+//! simple values go through a newtype struct with a reserved name, which the
+//! [Encoder](crate::ser::Encoder) and [Decoder](crate::de::Decoder) recognize and turn
+//! into a simple value header
+//!
+//! Other serde formats see that newtype struct as the number of the simple value
+
+use core::fmt;
+use serde::{
+    de::{self, Deserialize, Deserializer, Unexpected, Visitor},
+    ser::{Serialize, Serializer},
+};
+
+/// Name of the newtype struct used to pass simple values between [Simple] and the codec
+pub(crate) const SIMPLE_NAME: &str = "@@SIMPLE@@";
+
+/// A simple value given by its number
+/// # Considerations
+/// - Values 20 to 23 are false, true, null and undefined, they are written
+///   as such and any of them can be read back as a [Simple]
+/// - Values 24 to 31 are reserved by RFC 8949 section 3.3, the encoder refuses to
+///   write them and the decoder refuses to read them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Simple(pub u8);
+
+impl Serialize for Simple {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(SIMPLE_NAME, &self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Simple {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(SIMPLE_NAME, SimpleVisitor)
+    }
+}
+
+struct SimpleVisitor;
+
+impl<'de> Visitor<'de> for SimpleVisitor {
+    type Value = Simple;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a simple value outside of 24..32")
+    }
+
+    // Other formats hand out the number as a newtype
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        let value = u8::deserialize(deserializer)?;
+        self.visit_u8(value)
+    }
+
+    fn visit_u8<E: de::Error>(self, v: u8) -> Result<Self::Value, E> {
+        match v {
+            24..32 => Err(E::invalid_value(Unexpected::Unsigned(v as u64), &self)),
+            _ => Ok(Simple(v)),
+        }
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        match u8::try_from(v) {
+            Ok(value) => self.visit_u8(value),
+            Err(_) => Err(E::invalid_value(Unexpected::Unsigned(v), &self)),
+        }
+    }
+}
+
+/// The undefined simple value (0xF7)
+/// # Considerations
+/// - Types such as [Option] and `()` already read undefined as the absence of a value,
+///   use this type to tell it apart from null or to write it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Undefined;
+
+impl Serialize for Undefined {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Simple(23).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Undefined {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Simple::deserialize(deserializer)? {
+            Simple(23) => Ok(Undefined),
+            Simple(value) => Err(de::Error::invalid_value(
+                Unexpected::Unsigned(value as u64),
+                &"undefined",
+            )),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "ser", feature = "de", feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{
+        error::{DecodeError, EncodeError},
+        from_slice, to_vec,
+    };
+
+    #[test]
+    fn simple() {
+        let values = [Simple(0), Simple(20), Simple(23), Simple(32), Simple(255)];
+        let expected_cbor = Vec::from([
+            0xE0, // simple(0)
+            0xF4, // false
+            0xF7, // undefined
+            0xF8, 0x20, // simple(32)
+            0xF8, 0xFF, // simple(255)
+        ]);
+        let encoded: Vec<u8> = values
+            .iter()
+            .flat_map(|value| to_vec(value).unwrap())
+            .collect();
+        assert_eq!(encoded, expected_cbor);
+        assert_eq!(
+            from_slice::<Vec<Simple>>(&to_vec(&values).unwrap()).unwrap(),
+            values
+        );
+        assert!(matches!(
+            to_vec(&Simple(24)),
+            Err(EncodeError::ReservedSimpleValue(24))
+        ));
+        assert!(matches!(
+            from_slice::<Simple>(&[0xF8, 0x18]),
+            Err(DecodeError::ReservedSimpleValue(24))
+        ));
+        assert!(from_slice::<Simple>(&[0x01]).is_err());
+    }
+
+    #[test]
+    fn undefined() {
+        assert_eq!(to_vec(&Undefined).unwrap(), Vec::from([0xF7]));
+        assert_eq!(from_slice::<Undefined>(&[0xF7]).unwrap(), Undefined);
+        assert!(from_slice::<Undefined>(&[0xF6]).is_err());
+        assert_eq!(from_slice::<Option<u8>>(&[0xF7]).unwrap(), None);
+    }
+}
@@ -0,0 +1,169 @@
+//! Output destinations for the [Encoder](crate::ser::Encoder)
+//!
+//! The encoder does no buffering on its own, it writes every header and payload straight
+//! into its [Sink], so encoding into memory is a plain append and writers which are
+//! already buffered are not buffered twice
+
+use crate::error::EncodeError;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io;
+
+/// Destination of the bytes produced by the [Encoder](crate::ser::Encoder)
+pub trait Sink {
+    /// Write the whole `data` slice, failing if it cannot be written completely
+    fn write_all(&mut self, data: &[u8]) -> Result<(), EncodeError>;
+
+    /// Ensure all data written so far reached its final destination
+    fn flush(&mut self) -> Result<(), EncodeError> {
+        Ok(())
+    }
+}
+
+impl<S: Sink + ?Sized> Sink for &mut S {
+    fn write_all(&mut self, data: &[u8]) -> Result<(), EncodeError> {
+        (**self).write_all(data)
+    }
+
+    fn flush(&mut self) -> Result<(), EncodeError> {
+        (**self).flush()
+    }
+}
+
+/// Appends the output to the vector
+#[cfg(feature = "alloc")]
+impl Sink for Vec<u8> {
+    #[inline]
+    fn write_all(&mut self, data: &[u8]) -> Result<(), EncodeError> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Writes the output into a caller provided slice, failing with
+/// [EncodeError::BufferTooSmall] once it is full
+pub struct SliceSink<'buffer> {
+    buffer: &'buffer mut [u8],
+    position: usize,
+}
+
+impl<'buffer> SliceSink<'buffer> {
+    /// Construct a new sink, which will write from the start of `buffer`
+    pub fn new(buffer: &'buffer mut [u8]) -> Self {
+        Self {
+            buffer,
+            position: 0,
+        }
+    }
+
+    /// Number of bytes of the buffer used so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Return the part of the buffer which has been written
+    pub fn into_written(self) -> &'buffer mut [u8] {
+        &mut self.buffer[..self.position]
+    }
+}
+
+impl Sink for SliceSink<'_> {
+    #[inline]
+    fn write_all(&mut self, data: &[u8]) -> Result<(), EncodeError> {
+        let end = self.position + data.len();
+        match self.buffer.get_mut(self.position..end) {
+            Some(destination) => {
+                destination.copy_from_slice(data);
+                self.position = end;
+                Ok(())
+            }
+            None => Err(EncodeError::BufferTooSmall { required: None }),
+        }
+    }
+}
+
+/// Discards the output, only counting its length
+pub(crate) struct CountingSink {
+    count: usize,
+}
+
+impl CountingSink {
+    pub(crate) fn new() -> Self {
+        Self { count: 0 }
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Sink for CountingSink {
+    #[inline]
+    fn write_all(&mut self, data: &[u8]) -> Result<(), EncodeError> {
+        self.count += data.len();
+        Ok(())
+    }
+}
+
+/// Writes the output into a [std::io::Write] implementor
+/// # Considerations
+/// - This sink is not buffered, wrap the writer in a [std::io::BufWriter] if it
+///   performs a system call on every write (e.g. files and sockets)
+#[cfg(feature = "std")]
+pub struct IoSink<W: io::Write> {
+    writer: W,
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> IoSink<W> {
+    /// Construct a new sink, which will write into `W`
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Get a reference to the writer
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get a mutable reference to the writer
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Return the writer, the sink holds no data of its own so nothing is lost
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> Sink for IoSink<W> {
+    fn write_all(&mut self, data: &[u8]) -> Result<(), EncodeError> {
+        Ok(self.writer.write_all(data)?)
+    }
+
+    fn flush(&mut self) -> Result<(), EncodeError> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice_sink() {
+        let mut buffer = [0; 3];
+        let mut sink = SliceSink::new(&mut buffer);
+        sink.write_all(&[0x01, 0x02]).unwrap();
+        assert!(matches!(
+            sink.write_all(&[0x03, 0x04]),
+            Err(EncodeError::BufferTooSmall { required: None })
+        ));
+        sink.write_all(&[0x03]).unwrap();
+        assert_eq!(sink.position(), 3);
+        assert_eq!(sink.into_written(), &[0x01, 0x02, 0x03]);
+    }
+}
@@ -0,0 +1,340 @@
+//! Input sources for the [Decoder](crate::de::Decoder)
+//!
+//! A [Source] is the decoding counterpart of a [Sink](crate::sink::Sink), sources reading
+//! from memory hand out slices of their input directly so strings can be borrowed
+
+use crate::error::DecodeError;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+/// Bytes returned by a [Source], either borrowed from the whole input (so they
+/// live for `'de`) or from a temporary buffer of the source
+pub enum Reference<'de, 'a> {
+    Borrowed(&'de [u8]),
+    Copied(&'a [u8]),
+}
+
+impl AsRef<[u8]> for Reference<'_, '_> {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Reference::Borrowed(bytes) => bytes,
+            Reference::Copied(bytes) => bytes,
+        }
+    }
+}
+
+/// Origin of the bytes consumed by the [Decoder](crate::de::Decoder)
+pub trait Source<'de> {
+    /// Return the next byte without consuming it, [None] at the end of the input
+    fn peek_u8(&mut self) -> Result<Option<u8>, DecodeError>;
+
+    /// Fill the whole `buf`, failing with [DecodeError::UnexpectedEof] if the input ends before
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError>;
+
+    /// Read the next `length` bytes, failing with [DecodeError::UnexpectedEof] if the input ends before
+    fn read_slice(&mut self, length: u64) -> Result<Reference<'de, '_>, DecodeError>;
+
+    /// Discard the next `length` bytes, failing with [DecodeError::UnexpectedEof] if the input ends before
+    fn skip(&mut self, length: u64) -> Result<(), DecodeError> {
+        let mut discarded: [u8; 64] = [0; 64];
+        let mut remaining = length;
+        while remaining > 0 {
+            let chunk = remaining.min(discarded.len() as u64) as usize;
+            self.read_exact(&mut discarded[..chunk])?;
+            remaining -= chunk as u64;
+        }
+        Ok(())
+    }
+
+    /// Start keeping the bytes consumed from now on, to capture a data item as it is
+    /// encoded (read [crate::raw]), returns the mark to hand to [Source::finish_recording]
+    /// # Considerations
+    /// - Recordings can be nested, e.g. a raw value inside a map key, and are finished
+    ///   innermost first
+    fn start_recording(&mut self) -> usize {
+        0
+    }
+
+    /// Return the bytes consumed since the [Source::start_recording] call which returned
+    /// `mark` and stop keeping them, unless an outer recording still needs them, failing
+    /// with [DecodeError::RawValueUnsupported] if the source cannot keep them
+    fn finish_recording(&mut self, mark: usize) -> Result<Reference<'de, '_>, DecodeError> {
+        let _ = mark;
+        Err(DecodeError::RawValueUnsupported)
+    }
+}
+
+impl<'de, S: Source<'de> + ?Sized> Source<'de> for &mut S {
+    fn peek_u8(&mut self) -> Result<Option<u8>, DecodeError> {
+        (**self).peek_u8()
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError> {
+        (**self).read_exact(buf)
+    }
+
+    fn read_slice(&mut self, length: u64) -> Result<Reference<'de, '_>, DecodeError> {
+        (**self).read_slice(length)
+    }
+
+    fn skip(&mut self, length: u64) -> Result<(), DecodeError> {
+        (**self).skip(length)
+    }
+
+    fn start_recording(&mut self) -> usize {
+        (**self).start_recording()
+    }
+
+    fn finish_recording(&mut self, mark: usize) -> Result<Reference<'de, '_>, DecodeError> {
+        (**self).finish_recording(mark)
+    }
+}
+
+/// Reads the input from a slice, byte and text strings are borrowed from it
+pub struct SliceSource<'de> {
+    slice: &'de [u8],
+    position: usize,
+}
+
+impl<'de> SliceSource<'de> {
+    /// Construct a new source, which will read from the start of `slice`
+    pub fn new(slice: &'de [u8]) -> Self {
+        Self { slice, position: 0 }
+    }
+
+    /// Number of bytes of the slice consumed so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    fn take(&mut self, length: u64) -> Result<&'de [u8], DecodeError> {
+        let slice: &'de [u8] = self.slice;
+        let remaining = &slice[self.position..];
+        if length > remaining.len() as u64 {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        self.position += length as usize;
+        Ok(&remaining[..length as usize])
+    }
+}
+
+impl<'de> Source<'de> for SliceSource<'de> {
+    #[inline]
+    fn peek_u8(&mut self) -> Result<Option<u8>, DecodeError> {
+        Ok(self.slice.get(self.position).copied())
+    }
+
+    #[inline]
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError> {
+        buf.copy_from_slice(self.take(buf.len() as u64)?);
+        Ok(())
+    }
+
+    #[inline]
+    fn read_slice(&mut self, length: u64) -> Result<Reference<'de, '_>, DecodeError> {
+        self.take(length).map(Reference::Borrowed)
+    }
+
+    fn skip(&mut self, length: u64) -> Result<(), DecodeError> {
+        self.take(length).map(|_| ())
+    }
+
+    // The recorded bytes are still in the slice, so the mark is where they start in it
+    fn start_recording(&mut self) -> usize {
+        self.position
+    }
+
+    fn finish_recording(&mut self, mark: usize) -> Result<Reference<'de, '_>, DecodeError> {
+        let slice: &'de [u8] = self.slice;
+        Ok(Reference::Borrowed(&slice[mark..self.position]))
+    }
+}
+
+/// Reads the input from a slice which does not live for `'de`, so strings are handed out
+/// as copies, used to read again bytes recorded from another source
+#[cfg(feature = "alloc")]
+pub(crate) struct CopiedSource<'a>(SliceSource<'a>);
+
+#[cfg(feature = "alloc")]
+impl<'a> CopiedSource<'a> {
+    pub(crate) fn new(slice: &'a [u8]) -> Self {
+        Self(SliceSource::new(slice))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de> Source<'de> for CopiedSource<'_> {
+    fn peek_u8(&mut self) -> Result<Option<u8>, DecodeError> {
+        self.0.peek_u8()
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError> {
+        self.0.read_exact(buf)
+    }
+
+    fn read_slice(&mut self, length: u64) -> Result<Reference<'de, '_>, DecodeError> {
+        self.0.take(length).map(Reference::Copied)
+    }
+
+    fn skip(&mut self, length: u64) -> Result<(), DecodeError> {
+        self.0.skip(length)
+    }
+
+    fn start_recording(&mut self) -> usize {
+        self.0.position
+    }
+
+    fn finish_recording(&mut self, mark: usize) -> Result<Reference<'de, '_>, DecodeError> {
+        Ok(Reference::Copied(&self.0.slice[mark..self.0.position]))
+    }
+}
+
+/// Reads the input from a [std::io::BufRead] implementor, strings are copied into
+/// a buffer owned by the source
+/// # Considerations
+/// - Wrap plain [std::io::Read] implementors in a [std::io::BufReader], the decoder
+///   needs to look at the next byte without consuming it
+#[cfg(feature = "std")]
+pub struct IoSource<R: io::BufRead> {
+    reader: R,
+    scratch: Vec<u8>,
+    /// Number of recordings going on, read [Source::start_recording]
+    recording: usize,
+    /// Copy of the bytes consumed since the outermost recording started
+    recorded: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl<R: io::BufRead> IoSource<R> {
+    /// Construct a new source, which will read from `R`
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            scratch: Vec::new(),
+            recording: 0,
+            recorded: Vec::new(),
+        }
+    }
+
+    /// Return the reader, bytes it buffered past the decoded data items are kept in it
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(feature = "std")]
+fn io_error(error: io::Error) -> DecodeError {
+    match error.kind() {
+        io::ErrorKind::UnexpectedEof => DecodeError::UnexpectedEof,
+        _ => DecodeError::IO(error),
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, R: io::BufRead> Source<'de> for IoSource<R> {
+    fn peek_u8(&mut self) -> Result<Option<u8>, DecodeError> {
+        Ok(self.reader.fill_buf().map_err(io_error)?.first().copied())
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError> {
+        self.reader.read_exact(buf).map_err(io_error)?;
+        if self.recording > 0 {
+            self.recorded.extend_from_slice(buf);
+        }
+        Ok(())
+    }
+
+    // The buffer grows as data arrives so a bogus length cannot trigger a huge allocation
+    fn read_slice(&mut self, length: u64) -> Result<Reference<'de, '_>, DecodeError> {
+        self.scratch.clear();
+        let read = (&mut self.reader)
+            .take(length)
+            .read_to_end(&mut self.scratch)
+            .map_err(io_error)?;
+        if (read as u64) < length {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        if self.recording > 0 {
+            self.recorded.extend_from_slice(&self.scratch);
+        }
+        Ok(Reference::Copied(&self.scratch))
+    }
+
+    fn skip(&mut self, length: u64) -> Result<(), DecodeError> {
+        let mut skipped = (&mut self.reader).take(length);
+        let skipped = match self.recording {
+            0 => io::copy(&mut skipped, &mut io::sink()),
+            _ => io::copy(&mut skipped, &mut self.recorded),
+        }
+        .map_err(io_error)?;
+        if skipped < length {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        Ok(())
+    }
+
+    fn start_recording(&mut self) -> usize {
+        self.recording += 1;
+        self.recorded.len()
+    }
+
+    fn finish_recording(&mut self, mark: usize) -> Result<Reference<'de, '_>, DecodeError> {
+        self.recording -= 1;
+        self.scratch.clear();
+        self.scratch.extend_from_slice(&self.recorded[mark..]);
+        if self.recording == 0 {
+            self.recorded.clear();
+        }
+        Ok(Reference::Copied(&self.scratch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice_source() {
+        let input = [0x01, 0x02, 0x03];
+        let mut source = SliceSource::new(&input);
+        assert_eq!(source.peek_u8().unwrap(), Some(0x01));
+        assert!(matches!(
+            source.read_slice(2).unwrap(),
+            Reference::Borrowed(&[0x01, 0x02])
+        ));
+        assert!(matches!(source.skip(2), Err(DecodeError::UnexpectedEof)));
+        source.skip(1).unwrap();
+        assert_eq!(source.peek_u8().unwrap(), None);
+        assert_eq!(source.position(), 3);
+    }
+
+    #[test]
+    fn recording() {
+        let input = [0x01, 0x02, 0x03, 0x04];
+        let mut source = SliceSource::new(&input);
+        source.skip(1).unwrap();
+        let mark = source.start_recording();
+        source.read_exact(&mut [0]).unwrap();
+        source.skip(1).unwrap();
+        assert!(matches!(
+            source.finish_recording(mark).unwrap(),
+            Reference::Borrowed(&[0x02, 0x03])
+        ));
+        #[cfg(feature = "std")]
+        {
+            let mut source = IoSource::new(&input[..]);
+            source.skip(1).unwrap();
+            let outer = source.start_recording();
+            source.read_exact(&mut [0]).unwrap();
+            let inner = source.start_recording();
+            source.read_slice(1).unwrap();
+            assert_eq!(source.finish_recording(inner).unwrap().as_ref(), [0x03]);
+            source.skip(1).unwrap();
+            assert_eq!(
+                source.finish_recording(outer).unwrap().as_ref(),
+                [0x02, 0x03, 0x04]
+            );
+            assert!(source.recorded.is_empty());
+        }
+    }
+}
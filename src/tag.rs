@@ -0,0 +1,194 @@
+//! CBOR tags (major type 6)
+//!
+//! Tags are transparent to the serde data model, the [Decoder](crate::de::Decoder) skips
+//! them unless the type being deserialized asks for them. [Tagged] and [Required] do so
+//! through a tuple struct with a reserved name, which the [Encoder](crate::ser::Encoder)
+//! and [Decoder](crate::de::Decoder) recognize and turn into a tag header
+//!
+//! Other serde formats see that tuple struct as a `[tag, value]` pair
+
+use core::{fmt, marker::PhantomData};
+use serde::{
+    de::{self, Deserialize, Deserializer, SeqAccess, Unexpected, Visitor},
+    ser::{Serialize, SerializeTupleStruct, Serializer},
+};
+
+/// Name of the tuple struct used to pass tags between [Tagged] and the codec
+pub(crate) const TAG_NAME: &str = "@@TAGGED@@";
+
+/// Whether the data item starting with `initial_byte` can be the content of `tag`, as far
+/// as its type goes (RFC 8949 section 3.4), tags without requirements take anything
+#[cfg(feature = "de")]
+pub(crate) fn is_valid_content(tag: u64, initial_byte: u8) -> bool {
+    match tag {
+        // Date/time, URI, base64url, base64 and MIME message strings
+        0 | 32 | 33 | 34 | 36 => initial_byte >> 5 == 3,
+        // Epoch-based date/time, 0xF9..=0xFB = floats
+        1 => initial_byte >> 5 <= 1 || matches!(initial_byte, 0xF9..=0xFB),
+        // Bignums and encoded CBOR data items
+        2 | 3 | 24 => initial_byte >> 5 == 2,
+        // Decimal fractions and bigfloats
+        4 | 5 => initial_byte >> 5 == 4,
+        _ => true,
+    }
+}
+
+/// A value which may be enclosed in a tag
+/// # Considerations
+/// - When deserializing, `tag` holds the number of the outermost tag of the data item
+///   or [None] if it is not tagged, inner tags stay transparent to `value`
+/// - When serializing, a `tag` of [None] writes `value` as it is
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tagged<T> {
+    pub tag: Option<u64>,
+    pub value: T,
+}
+
+impl<T> Tagged<T> {
+    /// Construct a new value enclosed in `tag`
+    pub fn new(tag: u64, value: T) -> Self {
+        Self {
+            tag: Some(tag),
+            value,
+        }
+    }
+}
+
+impl<T: Serialize> Serialize for Tagged<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.tag {
+            Some(tag) => {
+                let mut tuple = serializer.serialize_tuple_struct(TAG_NAME, 2)?;
+                tuple.serialize_field(&tag)?;
+                tuple.serialize_field(&self.value)?;
+                tuple.end()
+            }
+            None => self.value.serialize(serializer),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Tagged<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple_struct(TAG_NAME, 2, TaggedVisitor(PhantomData))
+    }
+}
+
+struct TaggedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for TaggedVisitor<T> {
+    type Value = Tagged<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a tagged value")
+    }
+
+    // The decoder hands out untagged data items as a newtype
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        Ok(Tagged {
+            tag: None,
+            value: T::deserialize(deserializer)?,
+        })
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let tag = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let value = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(Tagged {
+            tag: Some(tag),
+            value,
+        })
+    }
+}
+
+/// A value which must be enclosed in the tag `TAG`, anything else is rejected when deserializing
+/// # Considerations
+/// - Useful for fields whose tag is fixed by a specification (e.g. tag 1 for epoch
+///   based date/time), the tag number does not have to be stored alongside the value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Required<T, const TAG: u64>(pub T);
+
+impl<T: Serialize, const TAG: u64> Serialize for Required<T, TAG> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple_struct(TAG_NAME, 2)?;
+        tuple.serialize_field(&TAG)?;
+        tuple.serialize_field(&self.0)?;
+        tuple.end()
+    }
+}
+
+impl<'de, T: Deserialize<'de>, const TAG: u64> Deserialize<'de> for Required<T, TAG> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let tagged = Tagged::<T>::deserialize(deserializer)?;
+        match tagged.tag {
+            Some(tag) if tag == TAG => Ok(Required(tagged.value)),
+            Some(tag) => Err(de::Error::invalid_value(
+                Unexpected::Unsigned(tag),
+                &ExpectedTag(TAG),
+            )),
+            None => Err(de::Error::invalid_type(
+                Unexpected::Other("untagged value"),
+                &ExpectedTag(TAG),
+            )),
+        }
+    }
+}
+
+struct ExpectedTag(u64);
+
+impl de::Expected for ExpectedTag {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "tag {}", self.0)
+    }
+}
+
+#[cfg(all(test, feature = "ser", feature = "de", feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{from_slice, to_vec};
+
+    #[test]
+    fn tagged() {
+        let value = Tagged::new(1, 1_000_000u32);
+        let encoded = to_vec(&value).unwrap();
+        // 0xC1 = tag(1) | 0x1A = unsigned integer in the next four bytes
+        assert_eq!(encoded, Vec::from([0xC1, 0x1A, 0x00, 0x0F, 0x42, 0x40]));
+        assert_eq!(from_slice::<Tagged<u32>>(&encoded).unwrap(), value);
+        // 0xD9 0xD9F7 = self described CBOR tag, the inner tag stays transparent
+        let nested = [0xD9, 0xD9, 0xF7, 0xC1, 0x01];
+        assert_eq!(
+            from_slice::<Tagged<u8>>(&nested).unwrap(),
+            Tagged::new(55799, 1)
+        );
+        let untagged = to_vec(&1u8).unwrap();
+        assert_eq!(
+            from_slice::<Tagged<u8>>(&untagged).unwrap(),
+            Tagged {
+                tag: None,
+                value: 1
+            }
+        );
+        // Plain types keep ignoring tags
+        assert_eq!(from_slice::<u32>(&encoded).unwrap(), 1_000_000);
+    }
+
+    #[test]
+    fn required() {
+        let encoded = to_vec(&Required::<_, 32>("https://example.com")).unwrap();
+        // 0xD8 0x20 = tag(32)
+        assert_eq!(encoded[..3], [0xD8, 0x20, 0x73]);
+        assert_eq!(
+            from_slice::<Required<String, 32>>(&encoded).unwrap().0,
+            "https://example.com"
+        );
+        assert!(from_slice::<Required<String, 33>>(&encoded).is_err());
+        assert!(from_slice::<Required<String, 32>>(&to_vec("untagged").unwrap()).is_err());
+    }
+}
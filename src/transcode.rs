@@ -0,0 +1,231 @@
+//! Streaming conversion between serde formats
+//!
+//! [transcode] drives any [Deserializer] straight into any [Serializer], so a CBOR
+//! [Decoder](crate::de::Decoder) can be re-emitted as JSON, YAML, MessagePack... and any of
+//! those decoders can feed the [Encoder](crate::ser::Encoder), item by item and without
+//! building an intermediate tree (e.g. a [Value](crate::value::Value))
+//!
+//! ```
+//! use serde_cbored::{de::Decoder, ser::Encoder, sink::SliceSink, source::SliceSource};
+//!
+//! // [_ "a", [true]] re-encoded, arrays keep their indefinite or definite length
+//! let input = [0x9F, 0x61, 0x61, 0x81, 0xF5, 0xFF];
+//! let mut output = [0u8; 6];
+//! let mut decoder = Decoder::new(SliceSource::new(&input));
+//! let mut encoder = Encoder::new(SliceSink::new(&mut output));
+//! serde_cbored::transcode::transcode(&mut decoder, &mut encoder).unwrap();
+//! assert_eq!(output, input);
+//! ```
+//!
+//! # Considerations
+//! - Memory use is bounded by the nesting depth and the longest string, arrays and maps are
+//!   passed on as they are read, with the length the source knows (if any)
+//! - Only what the serde data model holds goes through: the [Decoder](crate::de::Decoder)
+//!   drops tags (so bignums become byte strings) and fails on simple values other than
+//!   false, true, null and undefined, the last two both becoming unit
+//! - The [Encoder](crate::ser::Encoder) writes what it is given at the width of its Rust
+//!   type, and decoders hand out integers and floats as 64 bit ones, so re-encoding CBOR this
+//!   way does not keep their encoded width
+//! - Errors cross between the two sides through their message, so the caller gets an error
+//!   of the serializer holding the message of the original error
+
+use core::{cell::Cell, fmt};
+use serde::{
+    de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor},
+    ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer},
+};
+
+/// Serialize everything `deserializer` reads into `serializer`, read [crate::transcode]
+pub fn transcode<'de, D, S>(deserializer: D, serializer: S) -> Result<S::Ok, S::Error>
+where
+    D: Deserializer<'de>,
+    S: Serializer,
+{
+    Transcoder::new(deserializer).serialize(serializer)
+}
+
+/// Wraps a [Deserializer] so it serializes as the data it reads, for nesting transcoded
+/// data inside serialized data (e.g. as a struct field)
+/// # Considerations
+/// - It can only be serialized once, the deserializer is consumed in the process
+pub struct Transcoder<D>(Cell<Option<D>>);
+
+impl<'de, D: Deserializer<'de>> Transcoder<D> {
+    /// Construct a new transcoder reading from `deserializer`
+    pub fn new(deserializer: D) -> Self {
+        Self(Cell::new(Some(deserializer)))
+    }
+}
+
+impl<'de, D: Deserializer<'de>> Serialize for Transcoder<D> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let deserializer = self
+            .0
+            .take()
+            .expect("a Transcoder can only be serialized once");
+        deserializer
+            .deserialize_any(Transcode(serializer))
+            .map_err(ser::Error::custom)
+    }
+}
+
+/// Visitor serializing whatever it visits
+struct Transcode<S>(S);
+
+fn error<S: fmt::Display, E: de::Error>(error: S) -> E {
+    E::custom(error)
+}
+
+macro_rules! visit {
+    ($($visit:ident($type:ty) => $serialize:ident),* $(,)?) => {
+        $(
+            fn $visit<E: de::Error>(self, value: $type) -> Result<Self::Value, E> {
+                self.0.$serialize(value).map_err(error)
+            }
+        )*
+    };
+}
+
+impl<'de, S: Serializer> Visitor<'de> for Transcode<S> {
+    type Value = S::Ok;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    visit! {
+        visit_bool(bool) => serialize_bool,
+        visit_i8(i8) => serialize_i8,
+        visit_i16(i16) => serialize_i16,
+        visit_i32(i32) => serialize_i32,
+        visit_i64(i64) => serialize_i64,
+        visit_i128(i128) => serialize_i128,
+        visit_u8(u8) => serialize_u8,
+        visit_u16(u16) => serialize_u16,
+        visit_u32(u32) => serialize_u32,
+        visit_u64(u64) => serialize_u64,
+        visit_u128(u128) => serialize_u128,
+        visit_f32(f32) => serialize_f32,
+        visit_f64(f64) => serialize_f64,
+        visit_char(char) => serialize_char,
+        visit_str(&str) => serialize_str,
+        visit_bytes(&[u8]) => serialize_bytes,
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.0.serialize_unit().map_err(error)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.0.serialize_none().map_err(error)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.0
+            .serialize_some(&Transcoder::new(deserializer))
+            .map_err(error)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        self.0
+            .serialize_newtype_struct("<transcoded>", &Transcoder::new(deserializer))
+            .map_err(error)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut serializer = self.0.serialize_seq(seq.size_hint()).map_err(error)?;
+        while seq.next_element_seed(Element(&mut serializer))?.is_some() {}
+        serializer.end().map_err(error)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut serializer = self.0.serialize_map(map.size_hint()).map_err(error)?;
+        while map.next_key_seed(Key(&mut serializer))?.is_some() {
+            map.next_value_seed(Entry(&mut serializer))?;
+        }
+        serializer.end().map_err(error)
+    }
+}
+
+/// Seed serializing an array item as it is deserialized
+struct Element<'a, S>(&'a mut S);
+
+impl<'de, S: SerializeSeq> DeserializeSeed<'de> for Element<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.0
+            .serialize_element(&Transcoder::new(deserializer))
+            .map_err(error)
+    }
+}
+
+/// Seed serializing a map key as it is deserialized
+struct Key<'a, S>(&'a mut S);
+
+impl<'de, S: SerializeMap> DeserializeSeed<'de> for Key<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.0
+            .serialize_key(&Transcoder::new(deserializer))
+            .map_err(error)
+    }
+}
+
+/// Seed serializing a map value as it is deserialized
+struct Entry<'a, S>(&'a mut S);
+
+impl<'de, S: SerializeMap> DeserializeSeed<'de> for Entry<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.0
+            .serialize_value(&Transcoder::new(deserializer))
+            .map_err(error)
+    }
+}
+
+#[cfg(all(test, feature = "ser", feature = "de", feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::{de::Decoder, ser::Encoder, sink::SliceSink, source::SliceSource, to_vec};
+    use alloc::vec::Vec;
+
+    fn reencode(input: &[u8]) -> Vec<u8> {
+        let mut decoder = Decoder::new(SliceSource::new(input));
+        let mut output = Vec::new();
+        transcode(&mut decoder, &mut Encoder::new(&mut output)).unwrap();
+        decoder.end().unwrap();
+        output
+    }
+
+    #[test]
+    fn cbor_to_cbor() {
+        // {"a": [_ "b", true], h'01': null} keeps its shape
+        let input = b"\xA2\x61a\x9F\x61b\xF5\xFF\x41\x01\xF6";
+        assert_eq!(reencode(input), input);
+        // Integers are handed out as 64 bit ones
+        assert_eq!(reencode(b"\x20"), b"\x3B\x00\x00\x00\x00\x00\x00\x00\x00");
+        // Floats are rewritten the way the encoder writes f64
+        assert_eq!(
+            reencode(b"\xF9\x3E\x00"),
+            b"\xFB\x3F\xF8\x00\x00\x00\x00\x00\x00"
+        );
+        // Tags are dropped, so bignums become byte strings
+        assert_eq!(reencode(b"\xC0\x61x"), b"\x61x");
+        let bignum = to_vec(&(u64::MAX as u128 + 1)).unwrap();
+        assert_eq!(reencode(&bignum), &bignum[1..]);
+    }
+
+    #[test]
+    fn serializer_errors() {
+        let mut output = [0u8; 2];
+        let mut decoder = Decoder::new(SliceSource::new(b"\x83\x01\x02\x03"));
+        let mut encoder = Encoder::new(SliceSink::new(&mut output));
+        assert!(transcode(&mut decoder, &mut encoder).is_err());
+    }
+}
@@ -0,0 +1,527 @@
+//! Well-formedness checks of encoded data items (RFC 8949 Appendix C) which walk the heads
+//! without decoding anything, plus the validity checks of [DecodeOptions::check_validity],
+//! read [validate](crate::validate())
+
+use crate::{
+    de::DecodeOptions,
+    error::{DecodeError, ValidateError},
+    head::Head,
+    tag::is_valid_content,
+};
+#[cfg(feature = "alloc")]
+use crate::{de::DuplicateKeys, from_slice_with_options, value::Value};
+#[cfg(feature = "alloc")]
+use alloc::{collections::BTreeSet, vec::Vec};
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+/// Bytes the validator walks through, it only ever needs to look at heads
+pub(crate) trait Input {
+    /// Whether there are no bytes left
+    fn is_empty(&mut self) -> Result<bool, DecodeError>;
+
+    /// Fill the whole `buf`, failing with [DecodeError::UnexpectedEof] if the input ends before
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError>;
+
+    /// Discard the next `length` bytes, failing with [DecodeError::UnexpectedEof] if the input ends before
+    fn skip(&mut self, length: u64) -> Result<(), DecodeError>;
+}
+
+impl Input for &[u8] {
+    fn is_empty(&mut self) -> Result<bool, DecodeError> {
+        Ok(<[u8]>::is_empty(self))
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError> {
+        let (read, rest) = self
+            .split_at_checked(buf.len())
+            .ok_or(DecodeError::UnexpectedEof)?;
+        buf.copy_from_slice(read);
+        *self = rest;
+        Ok(())
+    }
+
+    fn skip(&mut self, length: u64) -> Result<(), DecodeError> {
+        *self = usize::try_from(length)
+            .ok()
+            .and_then(|length| self.get(length..))
+            .ok_or(DecodeError::UnexpectedEof)?;
+        Ok(())
+    }
+}
+
+/// A plain reader, which is not buffered so the one byte needed to find the end of the
+/// input is kept apart
+#[cfg(feature = "std")]
+pub(crate) struct ReaderInput<R> {
+    reader: R,
+    peeked: Option<u8>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> ReaderInput<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            peeked: None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn io_error(error: io::Error) -> DecodeError {
+    match error.kind() {
+        io::ErrorKind::UnexpectedEof => DecodeError::UnexpectedEof,
+        _ => DecodeError::IO(error),
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Input for ReaderInput<R> {
+    fn is_empty(&mut self) -> Result<bool, DecodeError> {
+        if self.peeked.is_none() {
+            let mut byte = [0u8; 1];
+            loop {
+                match self.reader.read(&mut byte) {
+                    Ok(0) => return Ok(true),
+                    Ok(_) => break,
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(error) => return Err(DecodeError::IO(error)),
+                }
+            }
+            self.peeked = Some(byte[0]);
+        }
+        Ok(false)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError> {
+        let buf = match (self.peeked.take(), buf) {
+            (Some(byte), [first, rest @ ..]) => {
+                *first = byte;
+                rest
+            }
+            (peeked, buf) => {
+                self.peeked = peeked;
+                buf
+            }
+        };
+        self.reader.read_exact(buf).map_err(io_error)
+    }
+
+    fn skip(&mut self, length: u64) -> Result<(), DecodeError> {
+        let length = match (length, self.peeked.take()) {
+            (0, peeked) => {
+                self.peeked = peeked;
+                return Ok(());
+            }
+            (length, Some(_)) => length - 1,
+            (length, None) => length,
+        };
+        let skipped =
+            io::copy(&mut (&mut self.reader).take(length), &mut io::sink()).map_err(io_error)?;
+        match skipped < length {
+            true => Err(DecodeError::UnexpectedEof),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Check every data item of `input`, stopping at the first one which is not well-formed
+/// (or not valid, read [DecodeOptions::check_validity])
+pub(crate) fn validate<I: Input>(input: I, options: DecodeOptions) -> Result<(), ValidateError> {
+    let mut validator = Validator::new(input, options, 0);
+    let result = validator.sequence();
+    result.map_err(|error| validator.error(error))
+}
+
+/// Find where the data item starting at `offset` in `bytes` ends by walking its heads,
+/// which only checks it is well-formed and not nested deeper than `max_depth`
+pub(crate) fn item_end(
+    bytes: &[u8],
+    offset: usize,
+    max_depth: usize,
+) -> Result<usize, ValidateError> {
+    let input = bytes.get(offset..).unwrap_or_default();
+    let options = DecodeOptions {
+        max_depth,
+        ..Default::default()
+    };
+    let mut validator = Validator::new(input, options, offset);
+    match validator.item() {
+        Ok(()) => Ok(validator.offset),
+        Err(error) => Err(validator.error(error)),
+    }
+}
+
+struct Validator<I> {
+    input: I,
+    options: DecodeOptions,
+    /// Bytes consumed so far
+    offset: usize,
+    /// Where the last head read starts, errors are reported there
+    head_offset: usize,
+    depth: usize,
+    /// Number of map keys being read, whose bytes are kept to look for duplicates
+    #[cfg(feature = "alloc")]
+    recording: usize,
+    #[cfg(feature = "alloc")]
+    recorded: Vec<u8>,
+}
+
+impl<I: Input> Validator<I> {
+    /// Construct a validator whose input starts at `offset` of the whole data
+    fn new(input: I, options: DecodeOptions, offset: usize) -> Self {
+        Self {
+            input,
+            options,
+            offset,
+            head_offset: offset,
+            depth: 0,
+            #[cfg(feature = "alloc")]
+            recording: 0,
+            #[cfg(feature = "alloc")]
+            recorded: Vec::new(),
+        }
+    }
+
+    /// Report `error` at the head being read
+    fn error(&self, error: DecodeError) -> ValidateError {
+        ValidateError {
+            offset: self.head_offset,
+            error,
+        }
+    }
+
+    fn sequence(&mut self) -> Result<(), DecodeError> {
+        while !self.input.is_empty()? {
+            self.item()?;
+        }
+        Ok(())
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError> {
+        self.input.read_exact(buf)?;
+        self.offset += buf.len();
+        #[cfg(feature = "alloc")]
+        if self.recording > 0 {
+            self.recorded.extend_from_slice(buf);
+        }
+        Ok(())
+    }
+
+    fn head(&mut self) -> Result<Head, DecodeError> {
+        self.head_offset = self.offset;
+        let mut bytes = [0u8; 9];
+        self.input.read_exact(&mut bytes[..1])?;
+        let size = Head::size(bytes[0])?;
+        self.input.read_exact(&mut bytes[1..size])?;
+        let head = Head::read(&bytes[..size])?;
+        self.offset += size;
+        #[cfg(feature = "alloc")]
+        if self.recording > 0 {
+            self.recorded.extend_from_slice(&bytes[..size]);
+        }
+        Ok(head)
+    }
+
+    /// Go over the contents of a string, which are only read when they have to be
+    /// looked at: text checked to be UTF-8 or a map key kept
+    fn string(&mut self, major_type: u8, length: u64) -> Result<(), DecodeError> {
+        let check_utf8 = major_type == 3 && self.options.check_validity;
+        #[cfg(feature = "alloc")]
+        let skip = !check_utf8 && self.recording == 0;
+        #[cfg(not(feature = "alloc"))]
+        let skip = !check_utf8;
+        if skip {
+            self.input.skip(length)?;
+            self.offset += length as usize;
+            return Ok(());
+        }
+        // Room for a chunk plus the start of a character split by the previous one
+        let mut buffer = [0u8; 64 + 3];
+        let (mut remaining, mut pending) = (length, 0);
+        while remaining > 0 {
+            let chunk = remaining.min(64) as usize;
+            self.read_exact(&mut buffer[pending..pending + chunk])?;
+            remaining -= chunk as u64;
+            let filled = pending + chunk;
+            pending = 0;
+            if check_utf8 && let Err(error) = core::str::from_utf8(&buffer[..filled]) {
+                // Only a character cut short at the end can be completed by the next piece
+                if error.error_len().is_some() {
+                    return Err(DecodeError::InvalidUtf8);
+                }
+                pending = filled - error.valid_up_to();
+                buffer.copy_within(error.valid_up_to()..filled, 0);
+            }
+        }
+        match pending {
+            0 => Ok(()),
+            _ => Err(DecodeError::InvalidUtf8),
+        }
+    }
+
+    fn recurse(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<(), DecodeError>,
+    ) -> Result<(), DecodeError> {
+        if self.depth >= self.options.max_depth {
+            return Err(DecodeError::DepthLimitExceeded);
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn item(&mut self) -> Result<(), DecodeError> {
+        let head = self.head()?;
+        self.item_after(head)
+    }
+
+    /// Check the rest of the data item starting with `head`
+    fn item_after(&mut self, head: Head) -> Result<(), DecodeError> {
+        match (head.major_type(), head.argument) {
+            (major_type @ (2 | 3), Some(length)) => self.string(major_type, length),
+            (major_type @ (2 | 3), None) => loop {
+                let chunk = self.head()?;
+                match (chunk.major_type(), chunk.argument) {
+                    // 0xFF = break byte
+                    (7, None) => return Ok(()),
+                    (chunk_type, Some(length)) if chunk_type == major_type => {
+                        self.string(major_type, length)?
+                    }
+                    _ => return Err(DecodeError::InvalidChunk),
+                }
+            },
+            (4, length) => self.recurse(|validator| validator.items(length, 1)),
+            (5, length) => self.recurse(|validator| validator.items(length, 2)),
+            (6, Some(tag)) => self.recurse(|validator| {
+                let content = validator.head()?;
+                if validator.options.check_validity && !is_valid_content(tag, content.initial_byte)
+                {
+                    return Err(DecodeError::InvalidTagContent(tag));
+                }
+                validator.item_after(content)
+            }),
+            (7, None) => Err(DecodeError::UnexpectedBreak),
+            _ => Ok(()),
+        }
+    }
+
+    fn items(&mut self, length: Option<u64>, items_per_entry: u64) -> Result<(), DecodeError> {
+        #[cfg(feature = "alloc")]
+        let mut keys = (items_per_entry == 2
+            && self.options.duplicate_key_policy() == DuplicateKeys::Reject)
+            .then(BTreeSet::new);
+        let mut remaining = length;
+        while remaining != Some(0) {
+            if let Some(remaining) = &mut remaining {
+                *remaining -= 1;
+            }
+            #[cfg(feature = "alloc")]
+            let key_offset = self.offset;
+            #[cfg(feature = "alloc")]
+            let start = keys.as_ref().map(|_| self.start_recording());
+            let head = self.head()?;
+            if length.is_none() && head.initial_byte == 0xFF {
+                // The break ends the map instead of starting a key
+                #[cfg(feature = "alloc")]
+                if start.is_some() {
+                    self.stop_recording();
+                }
+                return Ok(());
+            }
+            self.item_after(head)?;
+            #[cfg(feature = "alloc")]
+            if let (Some(keys), Some(start)) = (&mut keys, start) {
+                let key = self.finish_recording(start)?;
+                if !keys.insert(key) {
+                    self.head_offset = key_offset;
+                    return Err(DecodeError::DuplicateKey);
+                }
+            }
+            for _ in 1..items_per_entry {
+                self.item()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start keeping the bytes read, returns where they start in [Validator::recorded]
+    #[cfg(feature = "alloc")]
+    fn start_recording(&mut self) -> usize {
+        self.recording += 1;
+        self.recorded.len()
+    }
+
+    /// Decode the data item read since `start`, the bytes are kept while an outer map key
+    /// is still being read since they are part of it
+    #[cfg(feature = "alloc")]
+    fn finish_recording(&mut self, start: usize) -> Result<Value, DecodeError> {
+        let key = from_slice_with_options(&self.recorded[start..], self.options);
+        self.stop_recording();
+        key
+    }
+
+    /// Stop keeping the bytes read for the innermost map key being read
+    #[cfg(feature = "alloc")]
+    fn stop_recording(&mut self) {
+        self.recording -= 1;
+        if self.recording == 0 {
+            self.recorded.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(bytes: &[u8]) -> Result<(), (usize, DecodeError)> {
+        check_with_options(bytes, DecodeOptions::default())
+    }
+
+    fn check_with_options(
+        bytes: &[u8],
+        options: DecodeOptions,
+    ) -> Result<(), (usize, DecodeError)> {
+        let slice = validate(bytes, options);
+        #[cfg(feature = "std")]
+        {
+            let reader = validate(ReaderInput::new(bytes), options);
+            assert_eq!(
+                slice.as_ref().map_err(|error| error.offset),
+                reader.as_ref().map_err(|error| error.offset)
+            );
+        }
+        slice.map_err(|error| (error.offset, error.error))
+    }
+
+    #[test]
+    fn well_formed() {
+        for bytes in [
+            &b""[..],
+            b"\x00\x20\xF9\x3E\x00",
+            // {_ "a": [_ h'01', (_ h'02' h'')], "b": 1(0)}
+            b"\xBF\x61a\x9F\x41\x01\x5F\x41\x02\x40\xFF\xFF\x61b\xC1\x00\xFF",
+            // ["", {}, simple(32)]
+            b"\x83\x60\xA0\xF8\x20",
+        ] {
+            assert!(check(bytes).is_ok(), "{bytes:02X?}");
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let cases: [(&[u8], usize, DecodeError); 9] = [
+            (b"\x1C", 0, DecodeError::MalformedHeader(0x1C)),
+            (b"\x01\x1F", 1, DecodeError::MalformedHeader(0x1F)),
+            (b"\x82\x01", 2, DecodeError::UnexpectedEof),
+            (b"\x19\x01", 0, DecodeError::UnexpectedEof),
+            (b"\x81\x43\x01\x02", 1, DecodeError::UnexpectedEof),
+            (b"\xFF", 0, DecodeError::UnexpectedBreak),
+            (b"\xA1\x01\xFF", 2, DecodeError::UnexpectedBreak),
+            (b"\x5F\x41\x01\x61a\xFF", 3, DecodeError::InvalidChunk),
+            (b"\x7F\x7F\xFF\xFF", 1, DecodeError::InvalidChunk),
+        ];
+        for (bytes, offset, error) in cases {
+            let (found_offset, found) = check(bytes).unwrap_err();
+            assert_eq!(found_offset, offset, "{bytes:02X?}");
+            assert_eq!(
+                core::mem::discriminant(&found),
+                core::mem::discriminant(&error)
+            );
+        }
+        // An indefinite length map needs a value after each key
+        assert_eq!(check(b"\xBF\x01\xFF").unwrap_err().0, 2);
+        let deep = [0x81; 200];
+        assert!(matches!(
+            check(&deep),
+            Err((128, DecodeError::DepthLimitExceeded))
+        ));
+    }
+
+    #[test]
+    fn validity() {
+        let options = DecodeOptions {
+            check_validity: true,
+            ..Default::default()
+        };
+        let cases: [(&[u8], usize, DecodeError); 6] = [
+            // ["ok", "\xFF"]
+            (b"\x82\x62ok\x61\xFF", 4, DecodeError::InvalidUtf8),
+            // (_ "\xC3", "\xA9"), a character split between chunks
+            (b"\x7F\x61\xC3\x61\xA9\xFF", 1, DecodeError::InvalidUtf8),
+            // 0(0) and 2("1")
+            (b"\xC0\x00", 1, DecodeError::InvalidTagContent(0)),
+            (b"\x81\xC2\x61\x31", 2, DecodeError::InvalidTagContent(2)),
+            // {0: 1, [0]: {1: 0, 1: 0}}, found in a nested map
+            (
+                b"\xA2\x00\x01\x81\x00\xA2\x01\x00\x01\x00",
+                8,
+                DecodeError::DuplicateKey,
+            ),
+            // {_ "key": 1, "key": 2} with the second key in two chunks
+            (
+                b"\xBF\x63key\x01\x7F\x61k\x62ey\xFF\x02\xFF",
+                6,
+                DecodeError::DuplicateKey,
+            ),
+        ];
+        for (bytes, offset, error) in cases {
+            assert!(check(bytes).is_ok(), "{bytes:02X?}");
+            let (found_offset, found) = check_with_options(bytes, options).unwrap_err();
+            assert_eq!(found_offset, offset, "{bytes:02X?}");
+            assert_eq!(
+                core::mem::discriminant(&found),
+                core::mem::discriminant(&error)
+            );
+        }
+        // A long text string checked in pieces, with characters split between them
+        let mut text = b"\x79\x01\x2C".to_vec();
+        text.extend("é".repeat(150).as_bytes());
+        assert!(check_with_options(&text, options).is_ok());
+        // A map key holding a map with different keys
+        assert!(check_with_options(b"\xA2\xA1\x01\x02\x00\xA1\x02\x01\x00", options).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn duplicate_keys() {
+        // {1: "a", 2: "b", 1: "c"}
+        let bytes = b"\xA3\x01\x61a\x02\x61b\x01\x61c";
+        assert!(check(bytes).is_ok());
+        let options = DecodeOptions {
+            duplicate_keys: DuplicateKeys::Reject,
+            ..Default::default()
+        };
+        assert!(matches!(
+            check_with_options(bytes, options),
+            Err((7, DecodeError::DuplicateKey))
+        ));
+        // Duplicates are fine when the decoder is told which entry to keep
+        let options = DecodeOptions {
+            check_validity: true,
+            duplicate_keys: DuplicateKeys::KeepLast,
+            ..Default::default()
+        };
+        assert!(check_with_options(bytes, options).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn recording() {
+        let options = DecodeOptions {
+            check_validity: true,
+            ..Default::default()
+        };
+        // [{_ 0: 1}, "text"], no key is left being recorded once the map ends
+        let mut validator = Validator::new(&b"\x82\xBF\x00\x01\xFF\x64text"[..], options, 0);
+        validator.head().unwrap();
+        validator.item().unwrap();
+        assert_eq!(validator.recording, 0);
+        validator.item().unwrap();
+        assert!(validator.recorded.is_empty());
+    }
+}
@@ -0,0 +1,530 @@
+//! A dynamically typed CBOR data item
+//!
+//! [Value] can hold any well-formed CBOR data item, so it can decode documents whose shape
+//! is not known in advance and encode them back without losing tags, simple values nor
+//! map keys which are not text strings, [ValueRef] does the same borrowing byte and
+//! text strings from the input instead of copying them
+//!
+//! To see what the serde data model has no room for, both types ask the
+//! [Decoder](crate::de::Decoder) for it through a newtype struct with a reserved name,
+//! tags and simple values are then handed out as enum variants
+
+#[cfg(feature = "de")]
+mod deserializer;
+mod index;
+mod ord;
+mod path;
+#[cfg(feature = "ser")]
+mod serializer;
+
+pub use index::ValueIndex;
+pub use path::{Path, Segment};
+#[cfg(feature = "ser")]
+pub use serializer::{ArraySerializer, MapSerializer, ValueSerializer};
+
+use crate::{
+    simple::{SIMPLE_NAME, Simple, Undefined},
+    tag::{TAG_NAME, Tagged},
+};
+use alloc::{borrow::Cow, boxed::Box, string::String, vec::Vec};
+use core::fmt;
+use serde::{
+    de::{
+        self, Deserialize, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor,
+    },
+    ser::{Serialize, SerializeMap, Serializer},
+};
+
+/// Name of the newtype struct used by [Value] to ask the codec for tags and simple values
+pub(crate) const VALUE_NAME: &str = "@@VALUE@@";
+
+/// Any CBOR data item
+/// # Considerations
+/// - Integers out of the 64 bit range of major types 0 and 1 are written as bignums
+///   (tags 2 and 3), which are read back as [Value::Integer] when they fit in 128 bits
+/// - [Value::Simple] is meant for simple values other than false, true, null and
+///   undefined, those are read back as [Value::Bool], [Value::Null] and [Value::Undefined]
+/// - Floats are kept as doubles regardless of the precision they were encoded with, and
+///   written as doubles unless
+///   [EncodeOptions::shortest_floats](crate::ser::EncodeOptions::shortest_floats) is set
+/// - Integers, lengths and tag numbers are written in their shortest head and strings,
+///   arrays and maps with a definite length, whatever they were read with, so a data
+///   item only comes back byte for byte when it was encoded that way,
+///   [RawValue](crate::raw::RawValue) keeps the bytes as they are
+/// - Equality, ordering and hashing follow the bytewise order of the encoded data items
+///   (RFC 8949 section 4.2.1), so values can be map keys or sorted like a deterministic
+///   encoder sorts map keys, maps with the same entries in any order are equal
+#[derive(Debug, Clone)]
+pub enum Value {
+    Integer(i128),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    /// Entries are kept in the order they were found, keys may be any data item
+    Map(Vec<(Value, Value)>),
+    Tag(u64, Box<Value>),
+    Simple(u8),
+    Float(f64),
+    Bool(bool),
+    Null,
+    Undefined,
+}
+
+macro_rules! from_integer {
+    ($($integer:ty),*) => {
+        $(
+            impl From<$integer> for Value {
+                fn from(value: $integer) -> Self {
+                    Value::Integer(value as i128)
+                }
+            }
+        )*
+    };
+}
+
+from_integer!(i8, i16, i32, i64, i128, u8, u16, u32, u64);
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Value::Float(value as f64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Text(String::from(value))
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Text(value)
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
+        Value::Bytes(Vec::from(value))
+    }
+}
+
+/// Byte string literals (e.g. `b"cbor"`) are arrays
+impl<const N: usize> From<&[u8; N]> for Value {
+    fn from(value: &[u8; N]) -> Self {
+        Value::Bytes(Vec::from(value))
+    }
+}
+
+/// A vector of bytes is a byte string, not an array of integers
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Value::Bytes(value)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        Value::Array(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+/// False, true, null and undefined become their own variants
+impl From<Simple> for Value {
+    fn from(value: Simple) -> Self {
+        match value {
+            Simple(20) => Value::Bool(false),
+            Simple(21) => Value::Bool(true),
+            Simple(22) => Value::Null,
+            Simple(23) => Value::Undefined,
+            Simple(value) => Value::Simple(value),
+        }
+    }
+}
+
+impl From<Undefined> for Value {
+    fn from(_: Undefined) -> Self {
+        Value::Undefined
+    }
+}
+
+/// Serialize impls of [Value] and [ValueRef], whose variants have the same names
+macro_rules! serialize_value {
+    ($($value:ident$(<$lifetime:lifetime>)?),*) => {
+        $(
+            impl Serialize for $value$(<$lifetime>)? {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    match self {
+                        $value::Integer(value) => serializer.serialize_i128(*value),
+                        $value::Bytes(bytes) => serializer.serialize_bytes(bytes),
+                        $value::Text(text) => serializer.serialize_str(text),
+                        $value::Array(items) => serializer.collect_seq(items),
+                        $value::Map(entries) => {
+                            let mut map = serializer.serialize_map(Some(entries.len()))?;
+                            for (key, value) in entries {
+                                map.serialize_entry(key, value)?;
+                            }
+                            map.end()
+                        }
+                        $value::Tag(tag, value) => Tagged::new(*tag, value).serialize(serializer),
+                        $value::Simple(value) => Simple(*value).serialize(serializer),
+                        $value::Float(value) => serializer.serialize_f64(*value),
+                        $value::Bool(value) => serializer.serialize_bool(*value),
+                        $value::Null => serializer.serialize_unit(),
+                        $value::Undefined => Undefined.serialize(serializer),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+serialize_value!(Value, ValueRef<'_>);
+
+/// Read as a [ValueRef], whose strings are then copied unless they were already owned
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ValueRef::deserialize(deserializer).map(ValueRef::into_owned)
+    }
+}
+
+/// Integer of a bignum as the [Encoder](crate::ser::Encoder) writes it for 128 bit integers,
+/// [None] for any other tag or content, including bignums which are not in their shortest
+/// form so they are written back as they were
+fn bignum_integer(tag: u64, bytes: &[u8]) -> Option<i128> {
+    if !matches!(tag, 2 | 3) || !(9..=16).contains(&bytes.len()) || bytes[0] == 0 {
+        return None;
+    }
+    let magnitude = bytes
+        .iter()
+        .fold(0u128, |magnitude, byte| magnitude << 8 | *byte as u128);
+    let magnitude = i128::try_from(magnitude).ok()?;
+    Some(if tag == 2 { magnitude } else { -1 - magnitude })
+}
+
+/// Any CBOR data item, borrowing its byte and text strings from the input
+/// # Considerations
+/// - Strings are only borrowed when decoding from a slice (e.g. with
+///   [from_slice](crate::from_slice)), and not even then for indefinite length
+///   strings, whose chunks have to be concatenated
+/// - Read [Value] for how integers, simple values and floats are represented
+#[derive(Debug, Clone)]
+pub enum ValueRef<'a> {
+    Integer(i128),
+    Bytes(Cow<'a, [u8]>),
+    Text(Cow<'a, str>),
+    Array(Vec<ValueRef<'a>>),
+    /// Entries are kept in the order they were found, keys may be any data item
+    Map(Vec<(ValueRef<'a>, ValueRef<'a>)>),
+    Tag(u64, Box<ValueRef<'a>>),
+    Simple(u8),
+    Float(f64),
+    Bool(bool),
+    Null,
+    Undefined,
+}
+
+impl ValueRef<'_> {
+    /// Copy the borrowed strings, detaching the data item from the input
+    pub fn into_owned(self) -> Value {
+        match self {
+            ValueRef::Integer(value) => Value::Integer(value),
+            ValueRef::Bytes(bytes) => Value::Bytes(bytes.into_owned()),
+            ValueRef::Text(text) => Value::Text(text.into_owned()),
+            ValueRef::Array(items) => {
+                Value::Array(items.into_iter().map(ValueRef::into_owned).collect())
+            }
+            ValueRef::Map(entries) => Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect(),
+            ),
+            ValueRef::Tag(tag, value) => Value::Tag(tag, Box::new(value.into_owned())),
+            ValueRef::Simple(value) => Value::Simple(value),
+            ValueRef::Float(value) => Value::Float(value),
+            ValueRef::Bool(value) => Value::Bool(value),
+            ValueRef::Null => Value::Null,
+            ValueRef::Undefined => Value::Undefined,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ValueRef<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(VALUE_NAME, ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = ValueRef<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any CBOR data item")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(ValueRef::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(ValueRef::Integer(v as i128))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Self::Value, E> {
+        Ok(ValueRef::Integer(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(ValueRef::Integer(v as i128))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Self::Value, E> {
+        i128::try_from(v)
+            .map(ValueRef::Integer)
+            .map_err(|_| E::invalid_value(de::Unexpected::Other("128 bit integer"), &self))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(ValueRef::Float(v))
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        Ok(ValueRef::Text(Cow::Borrowed(v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(ValueRef::Text(Cow::Owned(String::from(v))))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(ValueRef::Text(Cow::Owned(v)))
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+        Ok(ValueRef::Bytes(Cow::Borrowed(v)))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(ValueRef::Bytes(Cow::Owned(Vec::from(v))))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(ValueRef::Bytes(Cow::Owned(v)))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(ValueRef::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        ValueRef::deserialize(deserializer)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(ValueRef::Null)
+    }
+
+    // Formats other than CBOR do not know the reserved name and hand out the data item as is
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(ValueRef::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(ValueRef::Map(entries))
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        let (name, variant): (&str, _) = data.variant()?;
+        match name {
+            TAG_NAME => variant.tuple_variant(2, TagVisitor),
+            SIMPLE_NAME => match variant.newtype_variant()? {
+                23 => Ok(ValueRef::Undefined),
+                value => Ok(ValueRef::Simple(value)),
+            },
+            _ => Err(de::Error::unknown_variant(name, &[TAG_NAME, SIMPLE_NAME])),
+        }
+    }
+}
+
+struct TagVisitor;
+
+impl<'de> Visitor<'de> for TagVisitor {
+    type Value = ValueRef<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a tagged data item")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let tag: u64 = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let value: ValueRef = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        match &value {
+            ValueRef::Bytes(bytes) => match bignum_integer(tag, bytes) {
+                Some(integer) => Ok(ValueRef::Integer(integer)),
+                None => Ok(ValueRef::Tag(tag, Box::new(value))),
+            },
+            _ => Ok(ValueRef::Tag(tag, Box::new(value))),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "ser", feature = "de", feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{from_slice, from_value, to_value, to_vec};
+    use serde::{Deserialize, Serialize};
+
+    fn document() -> Value {
+        Value::Map(Vec::from([
+            (Value::Integer(1), Value::Text(String::from("one"))),
+            (
+                Value::Bytes(Vec::from([0x00])),
+                Value::Array(Vec::from([
+                    Value::Integer(-1),
+                    Value::Integer(u64::MAX as i128 + 1),
+                    Value::Integer(-(u64::MAX as i128) - 2),
+                    Value::Integer(i128::MIN),
+                    Value::Float(1.5),
+                    Value::Bool(true),
+                    Value::Null,
+                    Value::Undefined,
+                    Value::Simple(32),
+                ])),
+            ),
+            (
+                Value::Text(String::from("t")),
+                Value::Tag(
+                    1,
+                    Box::new(Value::Tag(2, Box::new(Value::Bytes(Vec::from([0x01]))))),
+                ),
+            ),
+        ]))
+    }
+
+    #[test]
+    fn roundtrip() {
+        let encoded = to_vec(&document()).unwrap();
+        assert_eq!(from_slice::<Value>(&encoded).unwrap(), document());
+        // Integers use their shortest encoding
+        assert_eq!(to_vec(&Value::Integer(-256)).unwrap(), [0x38, 0xFF]);
+        assert_eq!(to_vec(&Value::Integer(500)).unwrap(), [0x19, 0x01, 0xF4]);
+    }
+
+    #[test]
+    fn conversion() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Point {
+            x: i32,
+            label: Option<String>,
+        }
+        let point = Point {
+            x: -300,
+            label: Some(String::from("a")),
+        };
+        let value = to_value(&point).unwrap();
+        assert_eq!(
+            value,
+            from_slice::<Value>(&to_vec(&point).unwrap()).unwrap()
+        );
+        assert_eq!(from_value::<Point>(value.clone()).unwrap(), point);
+        // Patch a field and encode the result
+        let Value::Map(mut entries) = value else {
+            panic!("expected a map")
+        };
+        entries[0].1 = Value::Integer(8);
+        let patched = to_vec(&Value::Map(entries)).unwrap();
+        assert_eq!(from_slice::<Point>(&patched).unwrap().x, 8);
+        // Types only CBOR knows about survive the conversion
+        assert_eq!(to_value(&document()).unwrap(), document());
+        assert_eq!(from_value::<Value>(document()).unwrap(), document());
+    }
+
+    #[test]
+    fn decode() {
+        // {_ "a": [_ h'01', 0.5], 0xF0: 0xF8FF}, float in half precision
+        let encoded = [
+            0xBF, 0x61, 0x61, 0x9F, 0x41, 0x01, 0xF9, 0x38, 0x00, 0xFF, 0xF0, 0xF8, 0xFF, 0xFF,
+        ];
+        assert_eq!(
+            from_slice::<ValueRef>(&encoded).unwrap().into_owned(),
+            from_slice::<Value>(&encoded).unwrap()
+        );
+        assert_eq!(
+            from_slice::<Value>(&encoded).unwrap(),
+            Value::Map(Vec::from([
+                (
+                    Value::Text(String::from("a")),
+                    Value::Array(Vec::from([
+                        Value::Bytes(Vec::from([0x01])),
+                        Value::Float(0.5)
+                    ]))
+                ),
+                (Value::Simple(16), Value::Simple(255)),
+            ]))
+        );
+    }
+
+    #[test]
+    fn borrowed() {
+        let encoded = to_vec(&document()).unwrap();
+        let value = from_slice::<ValueRef>(&encoded).unwrap();
+        assert_eq!(to_vec(&value).unwrap(), encoded);
+        assert_eq!(value.clone().into_owned(), document());
+        let ValueRef::Map(entries) = value else {
+            panic!("expected a map")
+        };
+        assert!(matches!(
+            &entries[0].1,
+            ValueRef::Text(Cow::Borrowed("one"))
+        ));
+        assert!(matches!(
+            &entries[1].0,
+            ValueRef::Bytes(Cow::Borrowed([0x00]))
+        ));
+        // The chunks of indefinite length strings have to be copied
+        let chunked = [0x7F, 0x61, 0x61, 0x61, 0x62, 0xFF];
+        assert!(matches!(
+            from_slice::<ValueRef>(&chunked).unwrap(),
+            ValueRef::Text(Cow::Owned(text)) if text == "ab"
+        ));
+    }
+}
@@ -0,0 +1,357 @@
+//! Deserializer reading from a [Value] instead of bytes
+
+use crate::{
+    de::bignum_magnitude,
+    error::DecodeError,
+    simple::SIMPLE_NAME,
+    tag::TAG_NAME,
+    value::{VALUE_NAME, Value},
+};
+use alloc::{boxed::Box, vec, vec::Vec};
+use serde::de::{
+    DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, VariantAccess, Visitor,
+    value::{BorrowedStrDeserializer, MapDeserializer, SeqDeserializer},
+};
+
+impl Value {
+    /// Human readable name of the data item, used in error messages
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::Bytes(_) => "byte string",
+            Value::Text(_) => "text string",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Tag(..) => "tag",
+            Value::Simple(_) => "simple value",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "boolean",
+            Value::Null => "null",
+            Value::Undefined => "undefined",
+        }
+    }
+
+    fn mismatch(&self, expected: &'static str) -> DecodeError {
+        DecodeError::TypeMismatch {
+            expected,
+            found: self.kind(),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DecodeError> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+/// Visit the items of an array, failing if the visitor does not consume all of them
+fn visit_array<'de, V>(items: Vec<Value>, visitor: V) -> Result<V::Value, DecodeError>
+where
+    V: Visitor<'de>,
+{
+    let mut access = SeqDeserializer::new(items.into_iter());
+    let value = visitor.visit_seq(&mut access)?;
+    access.end()?;
+    Ok(value)
+}
+
+// Mirrors the behaviour of the Decoder so converting a value into bytes and
+// decoding them gives the same result as converting it straight from the [Value]
+impl<'de> Deserializer<'de> for Value {
+    type Error = DecodeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Integer(value) => {
+                if let Ok(value) = u64::try_from(value) {
+                    visitor.visit_u64(value)
+                } else if let Ok(value) = i64::try_from(value) {
+                    visitor.visit_i64(value)
+                } else {
+                    visitor.visit_i128(value)
+                }
+            }
+            Value::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            Value::Text(text) => visitor.visit_string(text),
+            Value::Array(items) => visit_array(items, visitor),
+            Value::Map(entries) => {
+                let mut access = MapDeserializer::new(entries.into_iter());
+                let value = visitor.visit_map(&mut access)?;
+                access.end()?;
+                Ok(value)
+            }
+            // Tags are transparent to the serde data model
+            Value::Tag(_, value) => value.deserialize_any(visitor),
+            Value::Simple(value) => Err(DecodeError::UnsupportedSimpleValue(value)),
+            Value::Float(value) => visitor.visit_f64(value),
+            Value::Bool(value) => visitor.visit_bool(value),
+            // Both null and undefined are treated as the absence of a value
+            Value::Null | Value::Undefined => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_u128(visitor)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Integer(value) => visitor.visit_i128(value),
+            Value::Tag(tag @ (2 | 3), value) => {
+                let Value::Bytes(bytes) = *value else {
+                    return Err(value.mismatch("byte string"));
+                };
+                let magnitude = bignum_magnitude(&bytes)?;
+                match (tag, i128::try_from(magnitude)) {
+                    (2, _) => visitor.visit_u128(magnitude),
+                    (_, Ok(magnitude)) => visitor.visit_i128(-1 - magnitude),
+                    (_, Err(_)) => Err(DecodeError::IntegerOverflow),
+                }
+            }
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Null | Value::Undefined => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match (name, self) {
+            (VALUE_NAME, Value::Tag(tag, value)) => {
+                visitor.visit_enum(SpecialDeserializer::Tag(tag, *value))
+            }
+            (VALUE_NAME, Value::Simple(value)) => {
+                visitor.visit_enum(SpecialDeserializer::Simple(value))
+            }
+            (VALUE_NAME, Value::Undefined) => visitor.visit_enum(SpecialDeserializer::Simple(23)),
+            (VALUE_NAME, value) => value.deserialize_any(visitor),
+            (SIMPLE_NAME, Value::Simple(value)) => visitor.visit_u8(value),
+            (SIMPLE_NAME, Value::Bool(value)) => visitor.visit_u8(20 + value as u8),
+            (SIMPLE_NAME, Value::Null) => visitor.visit_u8(22),
+            (SIMPLE_NAME, Value::Undefined) => visitor.visit_u8(23),
+            (SIMPLE_NAME, value) => Err(value.mismatch("simple value")),
+            (_, value) => visitor.visit_newtype_struct(value),
+        }
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match (name, self) {
+            (TAG_NAME, Value::Tag(tag, value)) => {
+                visit_array(Vec::from([Value::Integer(tag as i128), *value]), visitor)
+            }
+            (TAG_NAME, value) => visitor.visit_newtype_struct(value),
+            (_, value) => value.deserialize_any(visitor),
+        }
+    }
+
+    // Unit variants are a text string holding the variant name and every other
+    // variant an array whose first element is the variant name
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Text(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Array(items) => {
+                let mut items = items.into_iter();
+                match items.next() {
+                    Some(variant) => visitor.visit_enum(EnumDeserializer { variant, items }),
+                    None => Err(DecodeError::LengthMismatch),
+                }
+            }
+            Value::Tag(_, value) => value.deserialize_enum(name, variants, visitor),
+            value => Err(value.mismatch("enum")),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple map struct identifier
+    }
+}
+
+/// Gives access to an enum variant encoded as an array
+struct EnumDeserializer {
+    variant: Value,
+    /// Items after the variant name
+    items: vec::IntoIter<Value>,
+}
+
+impl EnumDeserializer {
+    /// Take the only item left after the variant name
+    fn single_item(mut self) -> Result<Value, DecodeError> {
+        match (self.items.next(), self.items.next()) {
+            (Some(item), None) => Ok(item),
+            _ => Err(DecodeError::LengthMismatch),
+        }
+    }
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = DecodeError;
+    type Variant = Self;
+
+    fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = core::mem::replace(&mut self.variant, Value::Null);
+        Ok((seed.deserialize(variant)?, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for EnumDeserializer {
+    type Error = DecodeError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self.items.len() {
+            0 => Ok(()),
+            _ => Err(DecodeError::LengthMismatch),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.single_item()?)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visit_array(self.items.collect(), visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.single_item()?.deserialize_any(visitor)
+    }
+}
+
+/// Gives access to data items outside of the serde data model as an enum whose
+/// variant is named after the reserved name of its type, read [crate::value]
+enum SpecialDeserializer {
+    Tag(u64, Value),
+    Simple(u8),
+}
+
+impl<'de> EnumAccess<'de> for SpecialDeserializer {
+    type Error = DecodeError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let name = match self {
+            SpecialDeserializer::Tag(..) => TAG_NAME,
+            SpecialDeserializer::Simple(_) => SIMPLE_NAME,
+        };
+        let variant = seed.deserialize(BorrowedStrDeserializer::<DecodeError>::new(name))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for SpecialDeserializer {
+    type Error = DecodeError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Err(self.into_value().mismatch("unit variant"))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self {
+            SpecialDeserializer::Simple(value) => seed.deserialize(value.into_deserializer()),
+            special => Err(special.into_value().mismatch("newtype variant")),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            SpecialDeserializer::Tag(tag, value) => {
+                visit_array(Vec::from([Value::Integer(tag as i128), value]), visitor)
+            }
+            special => Err(special.into_value().mismatch("tuple variant")),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(self.into_value().mismatch("struct variant"))
+    }
+}
+
+impl SpecialDeserializer {
+    fn into_value(self) -> Value {
+        match self {
+            SpecialDeserializer::Tag(tag, value) => Value::Tag(tag, Box::new(value)),
+            SpecialDeserializer::Simple(value) => Value::Simple(value),
+        }
+    }
+}
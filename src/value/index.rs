@@ -0,0 +1,193 @@
+//! Indexing into arrays and maps with the `[]` operator
+
+use crate::value::Value;
+use alloc::string::String;
+use core::ops;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Types which can index into a [Value], integers address the items of an array
+/// by their position and the entries of a map by their integer key, text
+/// strings address the entries of a map by their text key
+/// # Considerations
+/// - Use a [Path](crate::value::Path) for other keys or to step into tags
+pub trait ValueIndex: private::Sealed {
+    #[doc(hidden)]
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value>;
+
+    #[doc(hidden)]
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value>;
+
+    /// Map key inserted by [IndexMut](ops::IndexMut) when there is no entry for it
+    #[doc(hidden)]
+    fn key(&self) -> Value;
+}
+
+fn position(key: i128, length: usize) -> Option<usize> {
+    usize::try_from(key)
+        .ok()
+        .filter(|position| *position < length)
+}
+
+macro_rules! integer_index {
+    ($($integer:ty),*) => {
+        $(
+            impl private::Sealed for $integer {}
+
+            impl ValueIndex for $integer {
+                fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+                    let key = *self as i128;
+                    match value {
+                        Value::Array(items) => items.get(position(key, items.len())?),
+                        Value::Map(entries) => entries
+                            .iter()
+                            .find(|(entry_key, _)| *entry_key == Value::Integer(key))
+                            .map(|(_, value)| value),
+                        _ => None,
+                    }
+                }
+
+                fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+                    let key = *self as i128;
+                    match value {
+                        Value::Array(items) => {
+                            let position = position(key, items.len())?;
+                            items.get_mut(position)
+                        }
+                        Value::Map(entries) => entries
+                            .iter_mut()
+                            .find(|(entry_key, _)| *entry_key == Value::Integer(key))
+                            .map(|(_, value)| value),
+                        _ => None,
+                    }
+                }
+
+                fn key(&self) -> Value {
+                    Value::Integer(*self as i128)
+                }
+            }
+        )*
+    };
+}
+
+// i32 is what integer literals fall back to, so `value[1]` works
+integer_index!(i32, i64, u32, u64, usize);
+
+impl private::Sealed for str {}
+
+impl ValueIndex for str {
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        match value {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(key, _)| matches!(key, Value::Text(key) if key == self))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        match value {
+            Value::Map(entries) => entries
+                .iter_mut()
+                .find(|(key, _)| matches!(key, Value::Text(key) if key == self))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn key(&self) -> Value {
+        Value::Text(String::from(self))
+    }
+}
+
+impl private::Sealed for String {}
+
+impl ValueIndex for String {
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        self.as_str().index_into(value)
+    }
+
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        self.as_str().index_into_mut(value)
+    }
+
+    fn key(&self) -> Value {
+        self.as_str().key()
+    }
+}
+
+impl<T: ?Sized + ValueIndex> private::Sealed for &T {}
+
+impl<T: ?Sized + ValueIndex> ValueIndex for &T {
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        (**self).index_into(value)
+    }
+
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        (**self).index_into_mut(value)
+    }
+
+    fn key(&self) -> Value {
+        (**self).key()
+    }
+}
+
+static NULL: Value = Value::Null;
+
+/// Missing items and values which are neither arrays nor maps give [Value::Null]
+impl<I: ValueIndex> ops::Index<I> for Value {
+    type Output = Value;
+
+    fn index(&self, index: I) -> &Value {
+        index.index_into(self).unwrap_or(&NULL)
+    }
+}
+
+/// Missing map entries are inserted with [Value::Null] as their value
+/// # Panics
+/// When indexing past the end of an array or into something other than an array or map
+impl<I: ValueIndex> ops::IndexMut<I> for Value {
+    fn index_mut(&mut self, index: I) -> &mut Value {
+        if index.index_into(self).is_none() {
+            match self {
+                Value::Map(entries) => entries.push((index.key(), Value::Null)),
+                _ => panic!("cannot index into {self:?}"),
+            }
+        }
+        index
+            .index_into_mut(self)
+            .expect("the item was found or inserted")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor;
+
+    #[test]
+    fn index() {
+        let mut value = cbor!({ 1 => [10, 20], "name" => "cbor" });
+        assert_eq!(value[1][0], cbor!(10));
+        assert_eq!(value["name"], cbor!("cbor"));
+        assert_eq!(value[-1], Value::Null);
+        assert_eq!(value[1][5], Value::Null);
+        value[1][1] = cbor!(21);
+        value["new"] = cbor!(true);
+        value[-1] = cbor!(false);
+        assert_eq!(
+            value,
+            cbor!({ 1 => [10, 21], "name" => "cbor", "new" => true, -1 => false })
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_out_of_bounds() {
+        let mut value = cbor!([1]);
+        value[1] = cbor!(2);
+    }
+}
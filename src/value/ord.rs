@@ -0,0 +1,369 @@
+//! Equality, ordering and hashing of data items by their encoding
+//!
+//! Two data items compare as the bytes the [Encoder](crate::ser::Encoder) writes for them
+//! with [sort_maps](crate::ser::EncodeOptions::sort_maps) and
+//! [shortest_floats](crate::ser::EncodeOptions::shortest_floats) enabled would, that is
+//! the bytewise lexicographic order of the deterministic encoding of RFC 8949 section
+//! 4.2.1, without encoding them: the initial byte and argument of their headers first,
+//! then their content
+
+use crate::{
+    float::{f64_to_f16, float_width},
+    value::{Value, ValueRef},
+};
+use alloc::vec::Vec;
+use core::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+};
+
+/// A data item seen through a reference, so [Value] and [ValueRef] share the same rules
+enum Item<'v, T> {
+    Integer(i128),
+    Bytes(&'v [u8]),
+    Text(&'v str),
+    Array(&'v [T]),
+    Map(&'v [(T, T)]),
+    Tag(u64, &'v T),
+    Simple(u8),
+    Float(f64),
+}
+
+trait Node: Sized {
+    fn item(&self) -> Item<'_, Self>;
+}
+
+impl Node for Value {
+    fn item(&self) -> Item<'_, Self> {
+        match self {
+            Value::Integer(value) => Item::Integer(*value),
+            Value::Bytes(bytes) => Item::Bytes(bytes),
+            Value::Text(text) => Item::Text(text),
+            Value::Array(items) => Item::Array(items),
+            Value::Map(entries) => Item::Map(entries),
+            Value::Tag(tag, value) => Item::Tag(*tag, value),
+            Value::Simple(value) => Item::Simple(*value),
+            Value::Float(value) => Item::Float(*value),
+            Value::Bool(value) => Item::Simple(20 + *value as u8),
+            Value::Null => Item::Simple(22),
+            Value::Undefined => Item::Simple(23),
+        }
+    }
+}
+
+impl Node for ValueRef<'_> {
+    fn item(&self) -> Item<'_, Self> {
+        match self {
+            ValueRef::Integer(value) => Item::Integer(*value),
+            ValueRef::Bytes(bytes) => Item::Bytes(bytes),
+            ValueRef::Text(text) => Item::Text(text),
+            ValueRef::Array(items) => Item::Array(items),
+            ValueRef::Map(entries) => Item::Map(entries),
+            ValueRef::Tag(tag, value) => Item::Tag(*tag, value),
+            ValueRef::Simple(value) => Item::Simple(*value),
+            ValueRef::Float(value) => Item::Float(*value),
+            ValueRef::Bool(value) => Item::Simple(20 + *value as u8),
+            ValueRef::Null => Item::Simple(22),
+            ValueRef::Undefined => Item::Simple(23),
+        }
+    }
+}
+
+/// Initial byte and argument of the shortest header for `argument`, comparing these
+/// pairs gives the same order as comparing the encoded headers
+fn header(major_type: u8, argument: u64) -> (u8, u64) {
+    let additional_info = match argument {
+        0..24 => argument as u8,
+        24..=0xFF => 24,
+        0x100..=0xFFFF => 25,
+        0x1_0000..=0xFFFF_FFFF => 26,
+        _ => 27,
+    };
+    (major_type | additional_info, argument)
+}
+
+/// Tag and magnitude of the bignum an integer out of the 64 bit range is written as
+fn bignum(integer: i128) -> Option<(u64, u128)> {
+    let (tag, magnitude) = match integer {
+        0.. => (2, integer as u128),
+        _ => (3, (-1 - integer) as u128),
+    };
+    (magnitude > u64::MAX as u128).then_some((tag, magnitude))
+}
+
+/// Content of the byte string holding the magnitude of a bignum, with no leading zeros
+fn bignum_bytes(magnitude: u128, buffer: &mut [u8; 16]) -> &[u8] {
+    *buffer = magnitude.to_be_bytes();
+    &buffer[(magnitude.leading_zeros() / 8) as usize..]
+}
+
+fn head<T>(item: &Item<T>) -> (u8, u64) {
+    match item {
+        Item::Integer(integer) => match bignum(*integer) {
+            // 0xC0 = tag major type
+            Some((tag, _)) => header(0xC0, tag),
+            // 0x00 = unsigned integer major type
+            None if *integer >= 0 => header(0x00, *integer as u64),
+            // 0x20 = negative integer major type
+            None => header(0x20, (-1 - *integer) as u64),
+        },
+        // 0x40 = byte string major type
+        Item::Bytes(bytes) => header(0x40, bytes.len() as u64),
+        // 0x60 = text string major type
+        Item::Text(text) => header(0x60, text.len() as u64),
+        // 0x80 = array major type
+        Item::Array(items) => header(0x80, items.len() as u64),
+        // 0xA0 = map major type
+        Item::Map(entries) => header(0xA0, entries.len() as u64),
+        // 0xC0 = tag major type
+        Item::Tag(tag, _) => header(0xC0, *tag),
+        // 0xE0 = simple value major type, 0xF8 followed by the value from 32 on
+        Item::Simple(value) => header(0xE0, *value as u64),
+        // 0xF9, 0xFA or 0xFB = the shortest float holding the value, every NaN is the same
+        // half precision quiet NaN
+        Item::Float(value) => match float_width(*value) {
+            25 => (0xF9, f64_to_f16(*value).unwrap_or_default() as u64),
+            26 => (0xFA, (*value as f32).to_bits() as u64),
+            _ => (0xFB, value.to_bits()),
+        },
+    }
+}
+
+/// Map entries in the order a deterministic encoding writes them
+fn sorted<T: Node>(entries: &[(T, T)]) -> Vec<&(T, T)> {
+    let mut sorted: Vec<_> = entries.iter().collect();
+    sorted.sort_by(|(key_a, value_a), (key_b, value_b)| {
+        compare(key_a, key_b).then_with(|| compare(value_a, value_b))
+    });
+    sorted
+}
+
+fn compare<T: Node>(a: &T, b: &T) -> Ordering {
+    let (a, b) = (a.item(), b.item());
+    head(&a).cmp(&head(&b)).then_with(|| match (a, b) {
+        (Item::Bytes(a), Item::Bytes(b)) => a.cmp(b),
+        (Item::Text(a), Item::Text(b)) => a.as_bytes().cmp(b.as_bytes()),
+        // Both have the same length, as their heads are the same
+        (Item::Array(a), Item::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal),
+        (Item::Map(a), Item::Map(b)) => sorted(a)
+            .into_iter()
+            .zip(sorted(b))
+            .map(|((key_a, value_a), (key_b, value_b))| {
+                compare(key_a, key_b).then_with(|| compare(value_a, value_b))
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal),
+        (Item::Tag(_, a), Item::Tag(_, b)) => compare(a, b),
+        // Only bignums get here, shorter magnitudes sort first as their byte strings do
+        (Item::Integer(a), Item::Integer(b)) => {
+            let magnitude = |integer: i128| bignum(integer).map(|(_, magnitude)| magnitude);
+            magnitude(a).cmp(&magnitude(b))
+        }
+        (Item::Integer(a), Item::Tag(_, b)) => compare_bignum(a, b),
+        (Item::Tag(_, a), Item::Integer(b)) => compare_bignum(b, a).reverse(),
+        _ => Ordering::Equal,
+    })
+}
+
+/// Compare a bignum [Item::Integer] with the content of a tag 2 or 3 written by hand
+fn compare_bignum<T: Node>(integer: i128, content: &T) -> Ordering {
+    let (_, magnitude) = bignum(integer).expect("integers only share a head with tags as bignums");
+    let mut buffer = [0; 16];
+    let bytes = bignum_bytes(magnitude, &mut buffer);
+    let content = content.item();
+    header(0x40, bytes.len() as u64)
+        .cmp(&head(&content))
+        .then_with(|| match content {
+            Item::Bytes(content) => bytes.cmp(content),
+            _ => Ordering::Equal,
+        })
+}
+
+fn hash<T: Node, H: Hasher>(value: &T, state: &mut H) {
+    let item = value.item();
+    head(&item).hash(state);
+    match item {
+        Item::Integer(integer) => {
+            if let Some((_, magnitude)) = bignum(integer) {
+                let mut buffer = [0; 16];
+                let bytes = bignum_bytes(magnitude, &mut buffer);
+                header(0x40, bytes.len() as u64).hash(state);
+                bytes.hash(state);
+            }
+        }
+        Item::Bytes(bytes) => bytes.hash(state),
+        Item::Text(text) => text.as_bytes().hash(state),
+        Item::Array(items) => items.iter().for_each(|item| hash(item, state)),
+        Item::Map(entries) => sorted(entries).into_iter().for_each(|(key, value)| {
+            hash(key, state);
+            hash(value, state);
+        }),
+        Item::Tag(_, value) => hash(value, state),
+        Item::Simple(_) | Item::Float(_) => {}
+    }
+}
+
+/// Items are equal when their encodings are, so maps with the same entries in a different
+/// order are equal, as are all NaNs, but `0.0` and `-0.0` are not
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        compare(self, other).is_eq()
+    }
+}
+
+impl Eq for Value {}
+
+/// Items are ordered by their encodings, as the keys of a map written with
+/// [sort_maps](crate::ser::EncodeOptions::sort_maps) and
+/// [shortest_floats](crate::ser::EncodeOptions::shortest_floats) are
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(self, other)
+    }
+}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash(self, state)
+    }
+}
+
+/// Same as the [PartialEq] implementation of [Value]
+impl PartialEq for ValueRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        compare(self, other).is_eq()
+    }
+}
+
+impl Eq for ValueRef<'_> {}
+
+impl PartialOrd for ValueRef<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ValueRef<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(self, other)
+    }
+}
+
+impl Hash for ValueRef<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash(self, state)
+    }
+}
+
+#[cfg(all(test, feature = "ser", feature = "de", feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{cbor, from_slice, ser::EncodeOptions, to_vec_with_options};
+    use std::collections::{BTreeSet, HashSet};
+
+    fn keys() -> Vec<Value> {
+        Vec::from([
+            cbor!(10),
+            cbor!(100),
+            cbor!(-1),
+            cbor!(-100),
+            cbor!(u64::MAX as i128 + 1),
+            cbor!(i128::MAX),
+            cbor!(-(u64::MAX as i128) - 2),
+            cbor!(b"z"),
+            cbor!(b"aa"),
+            cbor!("z"),
+            cbor!("aa"),
+            cbor!([1]),
+            cbor!([-1]),
+            cbor!({ 1 => 2 }),
+            cbor!(tag(1, 0)),
+            cbor!(tag(2, b"")),
+            cbor!(simple(0)),
+            cbor!(simple(32)),
+            cbor!(false),
+            cbor!(true),
+            cbor!(null),
+            cbor!(undefined),
+            cbor!(0.0),
+            cbor!(-0.0),
+            cbor!(1.5),
+            cbor!(f64::NAN),
+            cbor!(f64::NEG_INFINITY),
+            cbor!(100000.0),
+            cbor!(0.1f32),
+            cbor!(1.1),
+            cbor!(-1.1),
+        ])
+    }
+
+    #[test]
+    fn order() {
+        // The keys of a map written by a deterministic encoder come out sorted, floats in
+        // the shortest width holding them
+        let map = Value::Map(keys().into_iter().map(|key| (key, cbor!(null))).collect());
+        let options = EncodeOptions {
+            sort_maps: true,
+            shortest_floats: true,
+            ..Default::default()
+        };
+        let Value::Map(entries) = from_slice(&to_vec_with_options(&map, options).unwrap()).unwrap()
+        else {
+            panic!("expected a map")
+        };
+        let encoded: Vec<_> = entries.into_iter().map(|(key, _)| key).collect();
+        let mut sorted = keys();
+        sorted.sort();
+        assert_eq!(encoded.len(), sorted.len());
+        for (encoded, sorted) in encoded.iter().zip(&sorted) {
+            assert_eq!(
+                encoded.cmp(sorted),
+                Ordering::Equal,
+                "{encoded:?} {sorted:?}"
+            );
+        }
+        assert!(cbor!(24) > cbor!(23));
+        assert!(cbor!(-1) > cbor!(u64::MAX));
+        assert!(cbor!("b") > cbor!("a"));
+        assert!(cbor!("aa") > cbor!("b"));
+        // 0xFA 0x47C35000 before 0xFB 0x3FF199999999999A, 0xF9 0x7E00 before both
+        assert!(cbor!(100000.0) < cbor!(1.1));
+        assert!(cbor!(f64::NAN) < cbor!(100000.0));
+    }
+
+    #[test]
+    fn equality() {
+        assert_eq!(cbor!({ 1 => 2, "a" => [] }), cbor!({ "a" => [], 1 => 2 }));
+        assert_ne!(cbor!({ 1 => 2 }), cbor!({ 1 => 3 }));
+        assert_eq!(cbor!(f64::NAN), cbor!(-f64::NAN));
+        assert_ne!(cbor!(0.0), cbor!(-0.0));
+        assert_eq!(cbor!(simple(20)), cbor!(false));
+        // A bignum written by hand is the integer it stands for
+        let bignum = cbor!(tag(2, b"\x01\x00\x00\x00\x00\x00\x00\x00\x00"));
+        assert_eq!(bignum, cbor!(u64::MAX as i128 + 1));
+        let set: HashSet<Value> = [bignum, cbor!(u64::MAX as i128 + 1), cbor!(f64::NAN)]
+            .into_iter()
+            .collect();
+        assert_eq!(set.len(), 2);
+        let set: BTreeSet<Value> = keys().into_iter().chain(keys()).collect();
+        assert_eq!(set.len(), keys().len());
+        let set: HashSet<Value> = keys().into_iter().chain(keys()).collect();
+        assert_eq!(set.len(), keys().len());
+        let text = String::from("text");
+        assert_eq!(
+            ValueRef::Text(text.as_str().into()),
+            ValueRef::Text(text.clone().into())
+        );
+    }
+}
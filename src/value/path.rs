@@ -0,0 +1,304 @@
+//! Paths addressing the items nested in a [Value]
+
+use crate::{error::PathError, value::Value};
+use alloc::{string::String, vec::Vec};
+use core::str::FromStr;
+
+/// A step of a [Path]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    /// The value of a map entry with this key, integer keys also address the
+    /// items of an array by their position
+    Key(Value),
+    /// The content of a tag, [None] matches any tag number
+    Tag(Option<u64>),
+}
+
+/// A sequence of [Segment]s leading from a [Value] to one of its nested items
+///
+/// Paths can be parsed from a syntax akin to JSON Pointer (RFC 6901), each segment
+/// starting with a slash and the empty path addressing the value itself:
+/// - `/-7` is an integer, a map key or the position of an array item
+/// - `/h'0A0B'` is a byte string key given in hexadecimal
+/// - `/#` is the content of a tag and `/#18` the content of tag 18 only
+/// - `/text` is a text string key, enclose it in single quotes (`/'1'`) when it would be
+///   read as one of the above, `~0` and `~1` stand for `~` and `/` as in JSON Pointer
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Path(pub Vec<Segment>);
+
+impl From<Vec<Segment>> for Path {
+    fn from(segments: Vec<Segment>) -> Self {
+        Path(segments)
+    }
+}
+
+impl FromStr for Path {
+    type Err = PathError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        if path.is_empty() {
+            return Ok(Path::default());
+        }
+        let Some(path) = path.strip_prefix('/') else {
+            return Err(PathError::MissingSlash);
+        };
+        path.split('/')
+            .enumerate()
+            .map(|(index, segment)| parse_segment(segment).ok_or(PathError::InvalidSegment(index)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Path)
+    }
+}
+
+fn parse_segment(segment: &str) -> Option<Segment> {
+    let digits = segment.strip_prefix('-').unwrap_or(segment);
+    if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return segment
+            .parse()
+            .ok()
+            .map(|key| Segment::Key(Value::Integer(key)));
+    }
+    if let Some(tag) = segment.strip_prefix('#') {
+        return match tag {
+            "" => Some(Segment::Tag(None)),
+            _ => tag.parse().ok().map(|tag| Segment::Tag(Some(tag))),
+        };
+    }
+    if let Some(hex) = segment
+        .strip_prefix("h'")
+        .and_then(|hex| hex.strip_suffix('\''))
+    {
+        return decode_hex(hex).map(|bytes| Segment::Key(Value::Bytes(bytes)));
+    }
+    let text = match segment
+        .strip_prefix('\'')
+        .and_then(|text| text.strip_suffix('\''))
+    {
+        Some(quoted) => quoted,
+        None => segment,
+    };
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        match (char, chars.clone().next()) {
+            ('~', Some('0')) => unescaped.push('~'),
+            ('~', Some('1')) => unescaped.push('/'),
+            ('~', _) => return None,
+            (char, _) => {
+                unescaped.push(char);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    Some(Segment::Key(Value::Text(unescaped)))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+impl Segment {
+    /// Position of the array item this segment addresses
+    fn position(&self, length: usize) -> Option<usize> {
+        match self {
+            Segment::Key(Value::Integer(position)) => usize::try_from(*position)
+                .ok()
+                .filter(|position| *position < length),
+            _ => None,
+        }
+    }
+
+    fn step<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        match (self, value) {
+            (Segment::Key(key), Value::Map(entries)) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            (Segment::Key(_), Value::Array(items)) => items.get(self.position(items.len())?),
+            (Segment::Tag(expected), Value::Tag(tag, value))
+                if expected.is_none_or(|expected| expected == *tag) =>
+            {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    fn step_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        match (self, value) {
+            (Segment::Key(key), Value::Map(entries)) => entries
+                .iter_mut()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            (Segment::Key(_), Value::Array(items)) => {
+                let position = self.position(items.len())?;
+                items.get_mut(position)
+            }
+            (Segment::Tag(expected), Value::Tag(tag, value))
+                if expected.is_none_or(|expected| expected == *tag) =>
+            {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    /// Get the item at `path`, [None] if there is no such item
+    pub fn get(&self, path: &Path) -> Option<&Value> {
+        path.0
+            .iter()
+            .try_fold(self, |value, segment| segment.step(value))
+    }
+
+    /// Get a mutable reference to the item at `path`, [None] if there is no such item
+    pub fn get_mut(&mut self, path: &Path) -> Option<&mut Value> {
+        path.0
+            .iter()
+            .try_fold(self, |value, segment| segment.step_mut(value))
+    }
+
+    /// Same as [Value::get] parsing `path` first, [None] if it is not a valid [Path]
+    pub fn pointer(&self, path: &str) -> Option<&Value> {
+        self.get(&path.parse().ok()?)
+    }
+
+    /// Same as [Value::get_mut] parsing `path` first, [None] if it is not a valid [Path]
+    pub fn pointer_mut(&mut self, path: &str) -> Option<&mut Value> {
+        self.get_mut(&path.parse().ok()?)
+    }
+
+    /// Place `item` at `path`, returning the item it replaced
+    /// # Considerations
+    /// - A missing map key is appended as a new entry and the position right after the
+    ///   last item of an array appends to it, anything else missing is an error
+    pub fn set(&mut self, path: &Path, item: Value) -> Result<Option<Value>, PathError> {
+        let Some((last, parents)) = path.0.split_last() else {
+            return Ok(Some(core::mem::replace(self, item)));
+        };
+        let parent = parents
+            .iter()
+            .try_fold(self, |value, segment| segment.step_mut(value))
+            .ok_or(PathError::NotFound)?;
+        if last.step(parent).is_some() {
+            return Ok(last
+                .step_mut(parent)
+                .map(|current| core::mem::replace(current, item)));
+        }
+        match (last, parent) {
+            (Segment::Key(key), Value::Map(entries)) => {
+                entries.push((key.clone(), item));
+                Ok(None)
+            }
+            (Segment::Key(Value::Integer(position)), Value::Array(items))
+                if *position == items.len() as i128 =>
+            {
+                items.push(item);
+                Ok(None)
+            }
+            _ => Err(PathError::NotFound),
+        }
+    }
+
+    /// Remove the item at `path`, returning it
+    /// # Considerations
+    /// - The content of a tag and the value itself (the empty path) cannot be
+    ///   removed, both return [None]
+    pub fn remove(&mut self, path: &Path) -> Option<Value> {
+        let (last, parents) = path.0.split_last()?;
+        let parent = parents
+            .iter()
+            .try_fold(self, |value, segment| segment.step_mut(value))?;
+        match (last, parent) {
+            (Segment::Key(key), Value::Map(entries)) => {
+                let position = entries.iter().position(|(entry_key, _)| entry_key == key)?;
+                Some(entries.remove(position).1)
+            }
+            (Segment::Key(_), Value::Array(items)) => {
+                Some(items.remove(last.position(items.len())?))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor;
+
+    fn cose() -> Value {
+        // COSE_Sign1 style message: tag 18 enclosing [protected, unprotected, payload, signature]
+        cbor!(tag(18, [b"\xA1\x01\x26", { 4 => b"kid", "x/y" => 1 }, { -1 => [10, 20] }, b""]))
+    }
+
+    #[test]
+    fn parse() {
+        let path: Path = "/#18/-7/h'0aFF'/text/'1'/~0~1".parse().unwrap();
+        assert_eq!(
+            path.0,
+            [
+                Segment::Tag(Some(18)),
+                Segment::Key(Value::Integer(-7)),
+                Segment::Key(Value::Bytes(Vec::from([0x0A, 0xFF]))),
+                Segment::Key(Value::Text(String::from("text"))),
+                Segment::Key(Value::Text(String::from("1"))),
+                Segment::Key(Value::Text(String::from("~/"))),
+            ]
+        );
+        assert_eq!("".parse::<Path>().unwrap(), Path::default());
+        assert_eq!("1".parse::<Path>(), Err(PathError::MissingSlash));
+        assert_eq!("/#/h'0'".parse::<Path>(), Err(PathError::InvalidSegment(1)));
+        assert_eq!("/~2".parse::<Path>(), Err(PathError::InvalidSegment(0)));
+    }
+
+    #[test]
+    fn get() {
+        let value = cose();
+        assert_eq!(value.pointer("/#/1/4"), Some(&cbor!(b"kid")));
+        assert_eq!(value.pointer("/#18/1/x~1y"), Some(&cbor!(1)));
+        assert_eq!(value.pointer("/#18/2/-1/1"), Some(&cbor!(20)));
+        assert_eq!(value.pointer("/#17/2"), None);
+        assert_eq!(value.pointer("/#/2/-1/2"), None);
+        assert_eq!(value.pointer(""), Some(&value));
+    }
+
+    #[test]
+    fn set_and_remove() {
+        let mut value = cose();
+        let kid: Path = "/#/1/4".parse().unwrap();
+        assert_eq!(value.set(&kid, cbor!(b"new")), Ok(Some(cbor!(b"kid"))));
+        assert_eq!(
+            value.set(&"/#/1/h'00'".parse().unwrap(), cbor!(null)),
+            Ok(None)
+        );
+        assert_eq!(
+            value.set(&"/#/2/-1/2".parse().unwrap(), cbor!(30)),
+            Ok(None)
+        );
+        assert_eq!(
+            value.set(&"/#/2/-1/4".parse().unwrap(), cbor!(50)),
+            Err(PathError::NotFound)
+        );
+        assert_eq!(
+            value.set(&"/#/5/1".parse().unwrap(), cbor!(1)),
+            Err(PathError::NotFound)
+        );
+        assert_eq!(value.pointer("/#/2/-1"), Some(&cbor!([10, 20, 30])));
+        assert_eq!(value.remove(&"/#/2/-1/0".parse().unwrap()), Some(cbor!(10)));
+        assert_eq!(value.remove(&kid), Some(cbor!(b"new")));
+        assert_eq!(value.remove(&kid), None);
+        assert_eq!(
+            value.pointer("/#/1"),
+            Some(&cbor!({ "x/y" => 1, b"\x00" => null }))
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! Serializer producing a [Value] instead of bytes
+
+#[cfg(feature = "de")]
+use crate::raw::RAW_NAME;
+use crate::{error::EncodeError, simple::SIMPLE_NAME, tag::TAG_NAME, value::Value};
+use alloc::{borrow::ToOwned, boxed::Box, string::String, vec::Vec};
+use serde::ser::{
+    Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant, Serializer,
+};
+
+/// Serializer whose output is a [Value], read [to_value](crate::to_value)
+/// # Considerations
+/// - Values are laid out the way the [Encoder](crate::ser::Encoder) writes them (e.g. an
+///   enum variant with data becomes an array starting with the variant name)
+pub struct ValueSerializer;
+
+/// Collects the items of an array
+pub struct ArraySerializer {
+    items: Vec<Value>,
+    /// The items are the number and content of a tag, read [crate::tag]
+    tagged: bool,
+}
+
+/// Collects the entries of a map
+pub struct MapSerializer {
+    entries: Vec<(Value, Value)>,
+    /// Struct variants are wrapped in an array starting with the variant name
+    variant: Option<&'static str>,
+}
+
+impl Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = EncodeError;
+
+    type SerializeSeq = ArraySerializer;
+    type SerializeTuple = ArraySerializer;
+    type SerializeTupleStruct = ArraySerializer;
+    type SerializeTupleVariant = ArraySerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Integer(v as i128))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Integer(v as i128))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Integer(v as i128))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Integer(v as i128))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Integer(v as i128))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Integer(v as i128))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Integer(v as i128))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Integer(v as i128))
+    }
+
+    // Integers above i128::MAX stay the unsigned bignum the encoder would write
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        match i128::try_from(v) {
+            Ok(v) => Ok(Value::Integer(v)),
+            Err(_) => Ok(Value::Tag(
+                2,
+                Box::new(Value::Bytes(Vec::from(v.to_be_bytes()))),
+            )),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Float(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        let mut buf: [u8; 4] = [0; 4];
+        Ok(Value::Text(String::from(v.encode_utf8(&mut buf))))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Text(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Bytes(v.to_owned()))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Text(variant.to_owned()))
+    }
+
+    // Simple values arrive as a newtype struct holding their number, read [crate::simple],
+    // and raw values as one holding their bytes, which are decoded, read [crate::raw]
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let value = value.serialize(self)?;
+        #[cfg(feature = "de")]
+        if let (RAW_NAME, Value::Bytes(bytes)) = (name, &value) {
+            return crate::from_slice(bytes).map_err(<EncodeError as serde::ser::Error>::custom);
+        }
+        if name != SIMPLE_NAME {
+            return Ok(value);
+        }
+        match value {
+            Value::Integer(20) => Ok(Value::Bool(false)),
+            Value::Integer(21) => Ok(Value::Bool(true)),
+            Value::Integer(22) => Ok(Value::Null),
+            Value::Integer(23) => Ok(Value::Undefined),
+            Value::Integer(value @ 24..32) => Err(EncodeError::ReservedSimpleValue(value as u8)),
+            Value::Integer(value @ 0..256) => Ok(Value::Simple(value as u8)),
+            _ => Err(<EncodeError as serde::ser::Error>::custom(
+                "simple value out of range",
+            )),
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Value::Array(Vec::from([
+            Value::Text(variant.to_owned()),
+            value.serialize(self)?,
+        ])))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(ArraySerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            tagged: false,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    // Tags arrive as a tuple struct holding the tag number and the enclosed value
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Ok(ArraySerializer {
+            items: Vec::with_capacity(len),
+            tagged: name == TAG_NAME,
+        })
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        let mut items = Vec::with_capacity(len + 1);
+        items.push(Value::Text(variant.to_owned()));
+        Ok(ArraySerializer {
+            items,
+            tagged: false,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(MapSerializer {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(MapSerializer {
+            entries: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+}
+
+impl ArraySerializer {
+    fn push<T>(&mut self, value: &T) -> Result<(), EncodeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, EncodeError> {
+        if !self.tagged {
+            return Ok(Value::Array(self.items));
+        }
+        let mut items = self.items.into_iter();
+        match (items.next(), items.next(), items.next()) {
+            (Some(Value::Integer(tag)), Some(value), None) => match u64::try_from(tag) {
+                Ok(tag) => Ok(Value::Tag(tag, Box::new(value))),
+                Err(_) => Err(<EncodeError as serde::ser::Error>::custom(
+                    "tag number out of range",
+                )),
+            },
+            _ => Err(<EncodeError as serde::ser::Error>::custom(
+                "a tag needs a number and a value",
+            )),
+        }
+    }
+}
+
+impl MapSerializer {
+    fn finish(self) -> Result<Value, EncodeError> {
+        let map = Value::Map(self.entries);
+        match self.variant {
+            Some(variant) => Ok(Value::Array(Vec::from([
+                Value::Text(variant.to_owned()),
+                map,
+            ]))),
+            None => Ok(map),
+        }
+    }
+}
+
+impl SerializeSeq for ArraySerializer {
+    type Ok = Value;
+    type Error = EncodeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeTuple for ArraySerializer {
+    type Ok = Value;
+    type Error = EncodeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleStruct for ArraySerializer {
+    type Ok = Value;
+    type Error = EncodeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleVariant for ArraySerializer {
+    type Ok = Value;
+    type Error = EncodeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = EncodeError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.entries
+            .push((key.serialize(ValueSerializer)?, Value::Null));
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match self.entries.last_mut() {
+            Some((_, entry)) => {
+                *entry = value.serialize(ValueSerializer)?;
+                Ok(())
+            }
+            None => Err(<EncodeError as serde::ser::Error>::custom(
+                "map value serialized before its key",
+            )),
+        }
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeStruct for MapSerializer {
+    type Ok = Value;
+    type Error = EncodeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.entries.push((
+            Value::Text(key.to_owned()),
+            value.serialize(ValueSerializer)?,
+        ));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeStructVariant for MapSerializer {
+    type Ok = Value;
+    type Error = EncodeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
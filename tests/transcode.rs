@@ -0,0 +1,157 @@
+//! Transcoding large documents between CBOR and JSON keeps memory use bounded
+//!
+//! Lives apart from the unit tests since it needs a global allocator of its own to measure
+//! the memory in use
+
+use serde_cbored::{
+    de::Decoder, ser::Encoder, sink::IoSink, source::IoSource, to_vec, transcode::transcode,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io::{self, BufReader, Read, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Allocator keeping track of the bytes in use and the highest amount reached
+struct Counting;
+
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let in_use = IN_USE.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(in_use, Ordering::SeqCst);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        IN_USE.fetch_sub(layout.size(), Ordering::SeqCst);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Most bytes allocated at once while running `f`, on top of what was already in use
+fn peak_memory(f: impl FnOnce()) -> usize {
+    let before = IN_USE.load(Ordering::SeqCst);
+    PEAK.store(before, Ordering::SeqCst);
+    f();
+    PEAK.load(Ordering::SeqCst) - before
+}
+
+/// Reader producing a document made of `count` copies of `item`, generated as it is read
+struct Repeat {
+    open: &'static [u8],
+    item: Vec<u8>,
+    separator: &'static [u8],
+    close: &'static [u8],
+    count: usize,
+    /// Bytes of the document produced so far
+    position: usize,
+}
+
+impl Repeat {
+    fn len(&self) -> usize {
+        self.open.len()
+            + self.item.len() * self.count
+            + self.separator.len() * (self.count - 1)
+            + self.close.len()
+    }
+
+    fn byte(&self, mut position: usize) -> u8 {
+        if position < self.open.len() {
+            return self.open[position];
+        }
+        position -= self.open.len();
+        let stride = self.item.len() + self.separator.len();
+        match (position / stride < self.count, position % stride) {
+            (true, offset) if offset < self.item.len() => self.item[offset],
+            (true, offset) if position / stride < self.count - 1 => {
+                self.separator[offset - self.item.len()]
+            }
+            _ => self.close[position - (self.count * stride - self.separator.len())],
+        }
+    }
+}
+
+impl Read for Repeat {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = buf.len().min(self.len() - self.position);
+        for (offset, byte) in buf[..length].iter_mut().enumerate() {
+            *byte = self.byte(self.position + offset);
+        }
+        self.position += length;
+        Ok(length)
+    }
+}
+
+/// Writer counting the bytes written to it and dropping them
+#[derive(Default)]
+struct Count(usize);
+
+impl Write for Count {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+const COUNT: usize = 100_000;
+/// Far below the size of the documents, which take a few megabytes
+const BOUND: usize = 64 * 1024;
+
+#[test]
+fn bounded_memory() {
+    let json_item =
+        br#"{"id":"sensor","values":[1,-2,3.5,null],"nested":{"a":[[true],{"b":"c"}]}}"#;
+    let cbor_item =
+        to_vec(&serde_json::from_slice::<serde_json::Value>(json_item).unwrap()).unwrap();
+
+    // CBOR into JSON, [_ item, item, ...] becomes [item,item,...]
+    let cbor = Repeat {
+        open: &[0x9F],
+        item: cbor_item,
+        separator: &[],
+        close: &[0xFF],
+        count: COUNT,
+        position: 0,
+    };
+    let (input, mut output) = (cbor.len(), Count::default());
+    let peak = peak_memory(|| {
+        let mut decoder = Decoder::new(IoSource::new(BufReader::new(cbor)));
+        let mut serializer = serde_json::Serializer::new(&mut output);
+        transcode(&mut decoder, &mut serializer).unwrap();
+        decoder.end().unwrap();
+    });
+    assert!(input > 10 * BOUND);
+    assert_eq!(output.0, (json_item.len() + 1) * COUNT + 1);
+    assert!(peak < BOUND, "{peak} bytes in use");
+
+    // JSON into CBOR, the array has no known length so it stays indefinite
+    let json = Repeat {
+        open: b"[",
+        item: json_item.to_vec(),
+        separator: b",",
+        close: b"]",
+        count: COUNT,
+        position: 0,
+    };
+    let (input, mut output) = (json.len(), Count::default());
+    let peak = peak_memory(|| {
+        let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(json));
+        let mut encoder = Encoder::new(IoSink::new(&mut output));
+        transcode(&mut deserializer, &mut encoder).unwrap();
+        encoder.flush().unwrap();
+        deserializer.end().unwrap();
+    });
+    assert!(input > 10 * BOUND);
+    assert!(output.0 > COUNT);
+    assert!(peak < BOUND, "{peak} bytes in use");
+}